@@ -0,0 +1,45 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Embeds every file of `device_db/` as the built-in device database
+fn main() {
+    let db_dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("device_db");
+    println!("cargo:rerun-if-changed={}", db_dir.display());
+
+    let mut files = Vec::new();
+    if db_dir.is_dir() {
+        collect_files(&db_dir, &mut files);
+    }
+    files.sort();
+
+    let mut generated = String::from("pub(crate) const BUILTIN_DB: &[(&str, &str)] = &[\n");
+    for file in files {
+        println!("cargo:rerun-if-changed={}", file.display());
+        let name = file.file_name().unwrap().to_string_lossy();
+        generated.push_str(&format!(
+            "    ({:?}, include_str!({:?})),\n",
+            name,
+            file.display().to_string()
+        ));
+    }
+    generated.push_str("];\n");
+
+    let out_path = Path::new(&env::var("OUT_DIR").unwrap()).join("builtin_db.rs");
+    fs::write(out_path, generated).unwrap();
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(path).unwrap().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            files.push(path);
+        }
+    }
+}
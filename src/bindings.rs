@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    interfaces::{Button, ButtonState},
+    platform::{InputAction, ParseActionError, VirtualInputDevice},
+};
+
+/// Written as `Native`, `Disabled` or a key chord such as `Ctrl+Z`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) enum Binding {
+    /// Pass the button through as its native tablet button
+    Native,
+    /// Ignore the button
+    Disabled,
+    Action(InputAction),
+}
+
+impl TryFrom<String> for Binding {
+    type Error = ParseActionError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "Native" => Ok(Self::Native),
+            "Disabled" => Ok(Self::Disabled),
+            _ => Ok(Self::Action(value.parse()?)),
+        }
+    }
+}
+
+impl From<Binding> for String {
+    fn from(value: Binding) -> Self {
+        match value {
+            Binding::Native => "Native".to_string(),
+            Binding::Disabled => "Disabled".to_string(),
+            Binding::Action(action) => action.to_string(),
+        }
+    }
+}
+
+pub(crate) type Bindings = HashMap<Button, Binding>;
+
+/// Dispatches a button through its binding. The binding used on press is
+/// remembered in `held` so the release goes to the same place even if the
+/// bindings were reloaded in between.
+pub(crate) fn dispatch(
+    vinput: &mut impl VirtualInputDevice,
+    bindings: &Bindings,
+    held: &mut Bindings,
+    button: Button,
+    state: ButtonState,
+) {
+    let binding = match state {
+        ButtonState::Press => {
+            let binding = bindings.get(&button).cloned().unwrap_or(Binding::Native);
+            held.insert(button, binding.clone());
+            binding
+        }
+        ButtonState::Release => match held.remove(&button) {
+            Some(binding) => binding,
+            // nothing was dispatched on press
+            None => return,
+        },
+    };
+    match binding {
+        Binding::Native => vinput.submit_button(button, state),
+        Binding::Disabled => {}
+        Binding::Action(action) => {
+            debug!("{} {:?}, triggering {}.", button, state, action);
+            vinput.submit_action(&action, state == ButtonState::Press);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dispatch, Binding, Bindings};
+    use crate::{
+        device_info::DeviceInfo,
+        interfaces::{Button, ButtonState, PenStatus},
+        platform::{InputAction, VirtualInputDevice},
+    };
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl VirtualInputDevice for Recorder {
+        fn new(_device_info: &DeviceInfo) -> Self {
+            Self::default()
+        }
+
+        fn submit_cursor(&mut self, _update_info: PenStatus) {}
+
+        fn submit_action(&mut self, action: &InputAction, pressed: bool) {
+            self.events.push(format!("{} {}", action, pressed));
+        }
+
+        fn submit_button(&mut self, button: Button, state: ButtonState) {
+            self.events.push(format!("{} {:?}", button, state));
+        }
+
+        fn submit_scroll(&mut self, _ticks: i32) {}
+    }
+
+    fn bind(button: Button, binding: &str) -> Bindings {
+        Bindings::from([(button, Binding::try_from(binding.to_string()).unwrap())])
+    }
+
+    #[test]
+    fn bindings() {
+        let parse = |value: &str| Binding::try_from(value.to_string());
+        assert_eq!(parse("Native"), Ok(Binding::Native));
+        assert_eq!(parse("Disabled"), Ok(Binding::Disabled));
+        assert_eq!(parse("Alt+Tab").map(String::from).as_deref(), Ok("Alt+Tab"));
+        assert!(parse("Alt+Tba").is_err());
+    }
+
+    #[test]
+    fn release_goes_through_the_binding_used_on_press() {
+        let mut vinput = Recorder::default();
+        let mut held = Bindings::new();
+        let button = Button::Tablet(1);
+
+        dispatch(
+            &mut vinput,
+            &bind(button, "Ctrl+Z"),
+            &mut held,
+            button,
+            ButtonState::Press,
+        );
+        // bindings reloaded while the button is held
+        dispatch(
+            &mut vinput,
+            &bind(button, "Native"),
+            &mut held,
+            button,
+            ButtonState::Release,
+        );
+        dispatch(
+            &mut vinput,
+            &bind(button, "Native"),
+            &mut held,
+            button,
+            ButtonState::Press,
+        );
+        dispatch(
+            &mut vinput,
+            &bind(button, "Ctrl+Z"),
+            &mut held,
+            button,
+            ButtonState::Release,
+        );
+
+        assert_eq!(
+            vinput.events,
+            [
+                "Ctrl+Z true",
+                "Ctrl+Z false",
+                "Tablet1 Press",
+                "Tablet1 Release"
+            ]
+        );
+        assert!(held.is_empty());
+    }
+
+    #[test]
+    fn release_without_press_is_dropped() {
+        let mut vinput = Recorder::default();
+        let mut held = Bindings::new();
+        dispatch(
+            &mut vinput,
+            &Bindings::new(),
+            &mut held,
+            Button::PenPrimary,
+            ButtonState::Release,
+        );
+        assert!(vinput.events.is_empty());
+    }
+}
@@ -1,10 +1,8 @@
 // todo: log expect
 use std::path::PathBuf;
 
+use crate::{interfaces::DeviceID, protocol::Protocol, utils::LogExpect};
 use clap::{Parser, Subcommand};
-use log::debug;
-
-use crate::utils::LogExpect;
 
 #[derive(Parser, Debug)]
 #[command(author, version)]
@@ -19,6 +17,16 @@ pub(crate) struct CliOptions {
     /// (optional) Device database path
     #[arg(short, long, value_name = "path")]
     device_db: Option<PathBuf>,
+
+    /// (optional) Pidfile of the daemon
+    #[cfg(unix)]
+    #[arg(long, value_name = "path")]
+    pidfile: Option<PathBuf>,
+
+    /// (optional) Log file of the daemon
+    #[cfg(unix)]
+    #[arg(long, value_name = "path")]
+    log_file: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -27,10 +35,86 @@ pub(crate) enum Command {
     Run,
 
     /// Start as daemon
+    #[cfg(unix)]
     Deamon,
 
+    /// Stop the running daemon
+    #[cfg(unix)]
+    Stop,
+
+    /// Show whether the daemon is running
+    #[cfg(unix)]
+    Status,
+
     /// Install relative udev rules
     Install,
+
+    /// Preview the pressure curve configured for a device
+    Curve {
+        /// Device ID as vid:pid
+        device: DeviceID,
+
+        /// Number of rows to print
+        #[arg(short, long, default_value_t = 11)]
+        steps: usize,
+    },
+
+    /// Inspect the device database
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum DbCommand {
+    /// List entries and whether they are built in or user overrides
+    List,
+
+    /// Validate device info files, exits with an error code if any is invalid
+    Check {
+        /// File or directory to check, the device database path by default
+        path: Option<PathBuf>,
+    },
+
+    /// Convert OpenTabletDriver .json and libwacom .tablet/.stylus files
+    Import {
+        files: Vec<PathBuf>,
+
+        /// Directory to write to, the device database path by default
+        #[arg(short, long, value_name = "path")]
+        output: Option<PathBuf>,
+
+        /// Overwrite existing files
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Record an unknown tablet and infer its device info
+    New {
+        /// Device ID as vid:pid
+        device: DeviceID,
+
+        /// Vendor initialization to run before recording
+        #[arg(short, long, value_enum, default_value_t = Protocol::Generic)]
+        protocol: Protocol,
+
+        /// Interface to read reports from
+        #[arg(short, long)]
+        interface: Option<u8>,
+
+        /// Save the recorded packets to replay them later
+        #[arg(short, long, value_name = "path")]
+        record: Option<PathBuf>,
+
+        /// Analyse a recorded session instead of the connected device
+        #[arg(long, value_name = "path")]
+        replay: Option<PathBuf>,
+
+        /// Directory to write to, the device database path by default
+        #[arg(short, long, value_name = "path")]
+        output: Option<PathBuf>,
+    },
 }
 
 // todo: udev rules (linux only)
@@ -43,11 +127,20 @@ impl CliOptions {
     pub(crate) fn get_device_db(&self) -> &PathBuf {
         self.device_db.as_ref().unwrap()
     }
+
+    #[cfg(unix)]
+    pub(crate) fn get_pidfile(&self) -> &PathBuf {
+        self.pidfile.as_ref().unwrap()
+    }
+
+    #[cfg(unix)]
+    pub(crate) fn get_log_file(&self) -> &PathBuf {
+        self.log_file.as_ref().unwrap()
+    }
 }
 
 pub(crate) fn parse_cli_options() -> CliOptions {
     let mut options = CliOptions::parse();
-    debug!("Read command-line options: {:#?}", options);
     if options.config.is_none() {
         options.config=Some(dirs::config_dir().log_expect(
             "No config file provided, nor can the location of default config can be located.",
@@ -58,5 +151,25 @@ pub(crate) fn parse_cli_options() -> CliOptions {
             "No device database path provided, nor can the location of default device database can be located.",
         ).join("rablet").join("device_db"));
     };
+    #[cfg(unix)]
+    if options.pidfile.is_none() {
+        // per user, a shared directory like /tmp would let others plant the file
+        options.pidfile = Some(match dirs::runtime_dir() {
+            Some(runtime_dir) => runtime_dir.join("rablet.pid"),
+            None => dirs::state_dir()
+                .or_else(dirs::cache_dir)
+                .log_expect(
+                    "No pidfile provided, nor can the location of default pidfile can be located.",
+                )
+                .join("rablet")
+                .join("rablet.pid"),
+        });
+    };
+    #[cfg(unix)]
+    if options.log_file.is_none() {
+        options.log_file=Some(dirs::state_dir().or_else(dirs::cache_dir).log_expect(
+            "No log file provided, nor can the location of default log file can be located.",
+        ).join("rablet").join("rablet.log"));
+    };
     options
 }
@@ -1,21 +1,62 @@
-use crate::utils::LogExpect;
+use crate::{
+    bindings::Bindings,
+    interfaces::DeviceID,
+    mapping::{validate_size, AreaConfig, Orientation},
+    pressure::PressureConfig,
+    utils::LogExpect,
+    wheel::WheelConfig,
+};
 use log::{error, info};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     fs::{self, File},
     io::{self, Read, Write},
     path::PathBuf,
 };
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Config {}
+pub struct Config {
+    #[serde(default)]
+    pub(crate) screen: ScreenSize,
+    #[serde(default)]
+    pub(crate) devices: HashMap<DeviceID, DeviceConfig>,
+}
 
 impl Default for Config {
     fn default() -> Config {
-        Config {}
+        Config {
+            screen: Default::default(),
+            devices: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct ScreenSize {
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+}
+
+impl Default for ScreenSize {
+    fn default() -> ScreenSize {
+        ScreenSize {
+            width: 3840.0,
+            height: 2160.0,
+        }
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub(crate) struct DeviceConfig {
+    pub(crate) area: AreaConfig,
+    pub(crate) orientation: Orientation,
+    pub(crate) pressure: PressureConfig,
+    pub(crate) bindings: Bindings,
+    pub(crate) wheel: WheelConfig,
+}
+
 impl Config {
     fn save(&self, path: &PathBuf) {
         let content = serde_json::to_string(self)
@@ -57,20 +98,58 @@ impl Config {
 }
 
 impl Config {
-    pub(crate) fn load_config(config_path: &PathBuf) -> Config {
+    pub(crate) fn device_config(&self, id: &DeviceID) -> DeviceConfig {
+        self.devices.get(id).cloned().unwrap_or_default()
+    }
+
+    /// Used for reloading, where an invalid file keeps the current config
+    pub(crate) fn read_config(config_path: &PathBuf) -> Result<Config, String> {
+        let content = fs::read(config_path).map_err(|err| err.to_string())?;
+        Config::parse(&content)
+    }
+
+    fn parse(content: &[u8]) -> Result<Config, String> {
+        let config: Config = serde_json::from_slice(content).map_err(|err| err.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_size("screen", self.screen.width, self.screen.height)?;
+        for (id, device_config) in &self.devices {
+            device_config
+                .area
+                .validate()
+                .map_err(|err| format!("device {}: {}", id, err))?;
+        }
+        Ok(())
+    }
+
+    /// Without `interactive`, an invalid config is an error instead of a prompt on stdin
+    pub(crate) fn load_config(config_path: &PathBuf, interactive: bool) -> Config {
         if config_path.exists() {
             if config_path.is_file() {
                 let mut file_content = Vec::new();
                 File::open(config_path)
                     .log_expect(format!("Failed to read file {}.", config_path.display()))
                     .read_to_end(&mut file_content);
-                match serde_json::from_slice(&file_content) {
+                match Config::parse(&file_content) {
                     Ok(config) => config,
-                    Err(_err) => {
-                        error!("Invalid config file! Remove and create a new default? [y/N]");
+                    Err(err) if !interactive => {
+                        error!("Invalid config file {}: {}.", config_path.display(), err);
+                        panic!("Exiting.")
+                    }
+                    Err(err) => {
+                        error!(
+                            "Invalid config file: {}! Remove and create a new default? [y/N]",
+                            err
+                        );
                         Config::new_default_prompt(config_path)
                     }
                 }
+            } else if !interactive {
+                error!("{} is a directory!", config_path.display());
+                panic!("Exiting.")
             } else {
                 error!(
                     "{} is a directory! Remove and create a new default? [y/N]",
@@ -84,3 +163,26 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn empty_areas_are_rejected_on_load() {
+        let valid = br#"{ "devices": { "256c:006d": { "area": { "tablet": { "x": 0, "y": 0, "width": 100, "height": 50 } } } } }"#;
+        assert!(Config::parse(valid).is_ok());
+
+        let zero_width = br#"{ "devices": { "256c:006d": { "area": { "tablet": { "x": 0, "y": 0, "width": 0, "height": 50 } } } } }"#;
+        assert_eq!(
+            Config::parse(zero_width).unwrap_err(),
+            "device 256c:006d: tablet area width is 0, it must be positive"
+        );
+
+        let negative_output = br#"{ "devices": { "256c:006d": { "area": { "output": { "x": 0, "y": 0, "width": 1920, "height": -1080 } } } } }"#;
+        assert!(Config::parse(negative_output).is_err());
+
+        let empty_screen = br#"{ "screen": { "width": 0, "height": 0 } }"#;
+        assert!(Config::parse(empty_screen).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::fs::OpenOptionsExt,
+    },
+    path::{Path, PathBuf},
+    process::exit,
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+
+/// Slightly longer than the device pool takes to shut down
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Removes the pidfile when dropped
+pub(crate) struct Pidfile {
+    path: PathBuf,
+    /// Holds the lock until the daemon exits
+    _file: File,
+    /// Write end of the pipe the starting process waits on
+    ready: Option<File>,
+}
+
+impl Pidfile {
+    /// Lets the starting process exit successfully, call once the config has been loaded
+    pub(crate) fn notify_ready(&mut self) {
+        if let Some(mut ready) = self.ready.take() {
+            // the starting process may have been killed meanwhile
+            let _ = ready.write_all(&[1]);
+        }
+    }
+}
+
+impl Drop for Pidfile {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!("Failed to remove pidfile {}: {}.", self.path.display(), err);
+        }
+    }
+}
+
+/// Detaches from the terminal, redirects output to `log_file` and writes the pidfile.
+/// Runs before the logger is initialized, so errors are printed to stderr directly.
+/// The starting process only exits once `Pidfile::notify_ready` is called or the daemon dies.
+pub(crate) fn daemonize(pidfile: &Path, log_file: &Path) -> Pidfile {
+    if let Some(parent) = pidfile.parent() {
+        fs::create_dir_all(parent)
+            .unwrap_or_else(|err| fail(&format!("Failed to create {}", parent.display()), err));
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        // never truncate whatever a planted symlink points to
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(pidfile)
+        .unwrap_or_else(|err| fail(&format!("Failed to open {}", pidfile.display()), err));
+    // inherited by the forks, so the daemon holds it until it exits and concurrent starts fail
+    match try_lock(&file, libc::LOCK_EX) {
+        Ok(true) => {}
+        Ok(false) => {
+            match read_pid(&file) {
+                Some(pid) => eprintln!("rablet is already running with pid {}.", pid),
+                None => eprintln!("rablet is already running."),
+            }
+            exit(1);
+        }
+        Err(err) => fail(&format!("Failed to lock {}", pidfile.display()), err),
+    }
+    if let Some(parent) = log_file.parent() {
+        fs::create_dir_all(parent)
+            .unwrap_or_else(|err| fail(&format!("Failed to create {}", parent.display()), err));
+    }
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .unwrap_or_else(|err| fail(&format!("Failed to open {}", log_file.display()), err));
+    let null = File::open("/dev/null").unwrap_or_else(|err| fail("Failed to open /dev/null", err));
+
+    let (ready_read, ready_write) =
+        pipe().unwrap_or_else(|err| fail("Failed to create a pipe", err));
+
+    // the second fork makes sure the daemon can never acquire a controlling terminal again
+    if fork() {
+        drop(ready_write);
+        wait_until_ready(ready_read, log_file);
+    }
+    drop(ready_read);
+    if unsafe { libc::setsid() } == -1 {
+        fail("Failed to start a new session", io::Error::last_os_error());
+    }
+    if fork() {
+        exit(0);
+    }
+    // the working directory is kept so that relative paths from the command line still work
+
+    for (file, target) in [
+        (null.as_raw_fd(), libc::STDIN_FILENO),
+        (log.as_raw_fd(), libc::STDOUT_FILENO),
+        (log.as_raw_fd(), libc::STDERR_FILENO),
+    ] {
+        if unsafe { libc::dup2(file, target) } == -1 {
+            fail(
+                "Failed to redirect standard streams",
+                io::Error::last_os_error(),
+            );
+        }
+    }
+
+    let pid = std::process::id();
+    file.set_len(0)
+        .and_then(|_| (&file).write_all(format!("{}\n", pid).as_bytes()))
+        .unwrap_or_else(|err| fail(&format!("Failed to write {}", pidfile.display()), err));
+    Pidfile {
+        path: pidfile.to_path_buf(),
+        _file: file,
+        ready: Some(ready_write),
+    }
+}
+
+/// Exits the starting process once the daemon is ready, or with an error if it died before
+fn wait_until_ready(mut ready: File, log_file: &Path) -> ! {
+    let mut buffer = [0];
+    loop {
+        match ready.read(&mut buffer) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Ok(1) => {
+                println!("rablet started, logging to {}.", log_file.display());
+                exit(0)
+            }
+            // end of file, every write end was closed without a notification
+            _ => {
+                eprintln!("rablet failed to start, see {}.", log_file.display());
+                exit(1)
+            }
+        }
+    }
+}
+
+/// Read and write end
+fn pipe() -> io::Result<(File, File)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+}
+
+/// Returns true in the parent
+fn fork() -> bool {
+    match unsafe { libc::fork() } {
+        -1 => fail("Failed to fork", io::Error::last_os_error()),
+        0 => false,
+        _ => true,
+    }
+}
+
+fn fail(message: &str, err: io::Error) -> ! {
+    eprintln!("{}: {}.", message, err);
+    exit(1)
+}
+
+/// Returns false if another process holds a conflicting lock
+fn try_lock(file: &File, operation: libc::c_int) -> io::Result<bool> {
+    if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+fn read_pid(mut file: &File) -> Option<libc::pid_t> {
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    content.trim().parse().ok()
+}
+
+/// Pid from the pidfile while the daemon holds its lock, so a reused pid is never taken for rablet
+fn running_pid(pidfile: &Path) -> Option<libc::pid_t> {
+    let file = File::open(pidfile).ok()?;
+    match try_lock(&file, libc::LOCK_SH) {
+        Ok(false) => {}
+        Ok(true) => {
+            debug!("Ignoring stale pidfile {}.", pidfile.display());
+            return None;
+        }
+        Err(err) => {
+            warn!(
+                "Failed to check the lock of {}: {}.",
+                pidfile.display(),
+                err
+            );
+            return None;
+        }
+    }
+    // zero and negative pids would address whole process groups
+    read_pid(&file).filter(|pid| *pid > 0)
+}
+
+/// Returns whether the daemon was running and has stopped
+pub(crate) fn stop_daemon(pidfile: &Path) -> bool {
+    let Some(pid) = running_pid(pidfile) else {
+        println!("rablet is not running.");
+        return false;
+    };
+    if unsafe { libc::kill(pid, libc::SIGTERM) } == -1 {
+        println!(
+            "Failed to stop rablet with pid {}: {}.",
+            pid,
+            io::Error::last_os_error()
+        );
+        return false;
+    }
+    let start = Instant::now();
+    while start.elapsed() < STOP_TIMEOUT {
+        // the lock is released once the daemon has exited
+        if running_pid(pidfile).is_none() {
+            println!("Stopped rablet with pid {}.", pid);
+            return true;
+        }
+        thread::sleep(STOP_POLL_INTERVAL);
+    }
+    println!(
+        "rablet with pid {} is still running after {} seconds.",
+        pid,
+        STOP_TIMEOUT.as_secs()
+    );
+    false
+}
+
+/// Returns whether the daemon is running
+pub(crate) fn print_status(pidfile: &Path) -> bool {
+    match running_pid(pidfile) {
+        Some(pid) => {
+            println!("rablet is running with pid {}.", pid);
+            true
+        }
+        None => {
+            println!("rablet is not running.");
+            false
+        }
+    }
+}
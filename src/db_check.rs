@@ -0,0 +1,247 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde_json::{json, Value};
+
+use crate::{
+    device_info::{
+        builtin_raw_db, resolve, DbSource, DeviceInfo, RawDeviceDB, RawDeviceInfo, RawKey,
+    },
+    interfaces::Button,
+};
+
+#[derive(Debug, Default)]
+struct Report {
+    errors: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn error(&mut self, location: impl AsRef<str>, message: impl AsRef<str>) {
+        self.errors += 1;
+        println!("error: {}: {}", location.as_ref(), message.as_ref());
+    }
+
+    fn warning(&mut self, location: impl AsRef<str>, message: impl AsRef<str>) {
+        self.warnings += 1;
+        println!("warning: {}: {}", location.as_ref(), message.as_ref());
+    }
+}
+
+/// Validates every device info file under `path`, returns whether there were no errors
+pub(crate) fn check_db(path: &Path) -> bool {
+    let mut report = Report::default();
+    let mut files = Vec::new();
+    if path.is_dir() {
+        collect_files(path, &mut files, &mut report);
+    } else {
+        files.push(path.to_path_buf());
+    }
+    files.sort();
+
+    // built-in templates can be extended too
+    let mut raw_db = builtin_raw_db();
+    let mut texts = BTreeMap::new();
+    for file in &files {
+        let origin = file.display().to_string();
+        let Some(name) = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(err) => {
+                report.error(&origin, format!("Failed to read: {}.", err));
+                continue;
+            }
+        };
+        let value = match serde_json::from_str::<Value>(&text) {
+            Ok(value @ Value::Object(_)) => value,
+            Ok(_) => {
+                report.error(&origin, "Expected a JSON object.");
+                continue;
+            }
+            Err(err) => {
+                report_serde_error(&mut report, &origin, &err);
+                continue;
+            }
+        };
+        if let Some((existing, _)) = texts.get(&name) {
+            report.error(
+                &origin,
+                format!("Has the same name as {}, one of them is ignored.", existing),
+            );
+            continue;
+        }
+        raw_db.insert(
+            (DbSource::User, name.clone()),
+            RawDeviceInfo {
+                origin: origin.clone(),
+                value,
+                source: DbSource::User,
+            },
+        );
+        texts.insert(name, (origin, text));
+    }
+
+    let mut entries: Vec<(String, DeviceInfo)> = Vec::new();
+    for (name, (origin, text)) in &texts {
+        let key = (DbSource::User, name.clone());
+        let raw = &raw_db[&key];
+        if raw.value.get("id").is_none() {
+            check_template(&mut report, &raw_db, &key, origin);
+            continue;
+        }
+        let device_info = if raw.value.get("extends").is_none() {
+            // straight from the text to get line and column
+            serde_json::from_str::<DeviceInfo>(text).map_err(|err| {
+                report_serde_error(&mut report, origin, &err);
+            })
+        } else {
+            resolve(&raw_db, &key, &mut Vec::new())
+                .map_err(|err| report.error(origin, err))
+                .and_then(|resolved| {
+                    serde_json::from_value::<DeviceInfo>(Value::Object(resolved.fields)).map_err(
+                        |err| report.error(origin, format!("{} (after resolving extends)", err)),
+                    )
+                })
+        };
+        if let Ok(device_info) = device_info {
+            check_device_info(&mut report, origin, &device_info, false);
+            entries.push((origin.clone(), device_info));
+        }
+    }
+
+    for (index, (origin, device_info)) in entries.iter().enumerate() {
+        if let Some((existing, _)) = entries[..index].iter().find(|(_, existing)| {
+            (existing.id == device_info.id) && (existing.matcher == device_info.matcher)
+        }) {
+            report.error(
+                origin,
+                format!(
+                    "Duplicates ID {} of {}, add a \"match\" section to tell them apart.",
+                    device_info.id, existing
+                ),
+            );
+        }
+    }
+
+    println!(
+        "Checked {} files: {} errors, {} warnings.",
+        files.len(),
+        report.errors,
+        report.warnings
+    );
+    report.errors == 0
+}
+
+fn collect_files(path: &Path, files: &mut Vec<std::path::PathBuf>, report: &mut Report) {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(err) => {
+            report.error(
+                path.display().to_string(),
+                format!("Failed to read: {}.", err),
+            );
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files, report);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            files.push(path);
+        }
+    }
+}
+
+fn report_serde_error(report: &mut Report, origin: &str, err: &serde_json::Error) {
+    let message = err.to_string();
+    // the position is part of the location instead
+    let message = message.split(" at line ").next().unwrap_or(&message);
+    report.error(
+        format!("{}:{}:{}", origin, err.line(), err.column()),
+        message,
+    );
+}
+
+/// Templates are checked on their own so that their errors point at the template file
+fn check_template(report: &mut Report, raw_db: &RawDeviceDB, key: &RawKey, origin: &str) {
+    let resolved = match resolve(raw_db, key, &mut Vec::new()) {
+        Ok(resolved) => resolved,
+        Err(err) => {
+            report.error(origin, err);
+            return;
+        }
+    };
+    let mut fields = resolved.fields;
+    // templates have no id of their own, any will do for the remaining fields
+    fields.insert("id".to_string(), json!("0000:0000"));
+    match serde_json::from_value::<DeviceInfo>(Value::Object(fields)) {
+        Ok(device_info) => check_device_info(report, origin, &device_info, true),
+        Err(err) => report.error(origin, format!("{} (in a template)", err)),
+    }
+}
+
+/// Fields a template may leave to the entries extending it are only checked without `template`
+fn check_device_info(report: &mut Report, origin: &str, device_info: &DeviceInfo, template: bool) {
+    for (index, layout) in device_info.reports.iter().enumerate() {
+        for err in layout.errors() {
+            report.error(origin, format!("Report {}: {}.", index, err));
+        }
+        // a missing packet_length defaults to the span of the layouts
+        if (device_info.packet_length != 0) && (layout.span() > device_info.packet_length) {
+            report.error(
+                origin,
+                format!(
+                    "Report {} reads up to byte {}, but packet_length is {}.",
+                    index,
+                    layout.span(),
+                    device_info.packet_length
+                ),
+            );
+        }
+        let mut seen: Vec<Button> = Vec::new();
+        for field in &layout.buttons {
+            if seen.contains(&field.button) {
+                report.error(
+                    origin,
+                    format!("Report {} declares button {} twice.", index, field.button),
+                );
+            }
+            seen.push(field.button);
+            if !template && !device_info.button_available.contains(&field.button) {
+                report.warning(
+                    origin,
+                    format!(
+                        "Report {} declares button {}, which is missing from button_available and will be ignored.",
+                        index, field.button
+                    ),
+                );
+            }
+        }
+    }
+
+    let mut seen: Vec<Button> = Vec::new();
+    for button in &device_info.button_available {
+        if seen.contains(button) {
+            report.error(origin, format!("button_available lists {} twice.", button));
+        }
+        seen.push(*button);
+    }
+
+    if !template
+        && !device_info.reports.is_empty()
+        && ((device_info.width <= 0.0) || (device_info.height <= 0.0))
+    {
+        report.error(
+            origin,
+            "width and height are required unless the layout comes from the report descriptor.",
+        );
+    }
+}
@@ -0,0 +1,670 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Map, Value};
+
+use crate::{device_match::FIRMWARE_STRING_INDEX, mapping::MM_PER_INCH};
+
+const OTD_PARSER_HUION: &str = "UCLogic";
+const OTD_PARSER_XPPEN: &str = "XP_Pen";
+const HUION_TEMPLATE: &str = "huion-v2-common";
+const XPPEN_TEMPLATE: &str = "xppen-deco-common";
+/// Tablet buttons covered by the pad report of the XP-Pen template
+const XPPEN_PAD_BUTTONS: u64 = 8;
+/// Huion pad reports carry the button bitmap in bytes 4 and 5
+const HUION_PAD_BUTTONS: u64 = 16;
+/// libwacom doesn't describe rings, this is what Wacom rings report
+const RING_POSITIONS: u8 = 72;
+
+/// A translated device info file and what couldn't be carried over
+struct Imported {
+    name: String,
+    entry: Map<String, Value>,
+    untranslated: Vec<String>,
+}
+
+/// Sections of a GLib key file, as used by libwacom
+type KeyFile = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Converts OpenTabletDriver `.json` and libwacom `.tablet` files into `output`,
+/// `.stylus` files among `files` describe the styli of the tablets
+pub(crate) fn import_files(files: &[PathBuf], output: &Path, force: bool) -> bool {
+    let mut styli = Vec::new();
+    for file in files.iter().filter(|file| has_extension(file, "stylus")) {
+        match fs::read_to_string(file) {
+            Ok(text) => styli.push(parse_key_file(&text)),
+            Err(err) => {
+                println!("error: {}: Failed to read: {}.", file.display(), err);
+                return false;
+            }
+        }
+    }
+
+    let mut success = true;
+    for file in files.iter().filter(|file| !has_extension(file, "stylus")) {
+        let imported = fs::read_to_string(file)
+            .map_err(|err| format!("Failed to read: {}.", err))
+            .and_then(|text| {
+                if has_extension(file, "tablet") {
+                    import_libwacom(&parse_key_file(&text), &styli)
+                } else {
+                    serde_json::from_str::<Value>(&text)
+                        .map_err(|err| err.to_string())
+                        .and_then(|value| import_otd(&value))
+                }
+            });
+        match imported {
+            Ok(imported) => {
+                for imported in imported {
+                    success &= write_imported(&imported, output, force);
+                }
+            }
+            Err(err) => {
+                println!("error: {}: {}", file.display(), err);
+                success = false;
+            }
+        }
+    }
+    success
+}
+
+fn has_extension(file: &Path, extension: &str) -> bool {
+    file.extension()
+        .is_some_and(|file_extension| file_extension == extension)
+}
+
+fn write_imported(imported: &Imported, output: &Path, force: bool) -> bool {
+    let path = output.join(format!("{}.json", imported.name));
+    if path.exists() && !force {
+        println!(
+            "error: {} already exists, pass --force to overwrite it.",
+            path.display()
+        );
+        return false;
+    }
+    let content = serde_json::to_string_pretty(&imported.entry).unwrap_or_default();
+    if let Err(err) = fs::create_dir_all(output).and_then(|_| fs::write(&path, content + "\n")) {
+        println!("error: {}: Failed to write: {}.", path.display(), err);
+        return false;
+    }
+    println!("Wrote {}", path.display());
+    for field in &imported.untranslated {
+        println!("  untranslated: {}", field);
+    }
+    true
+}
+
+/// File name friendly version of a model name
+fn slug(name: &str) -> String {
+    let slug: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    slug.split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn button_names(pen_buttons: u64, eraser: bool, tablet_buttons: u64) -> Vec<Value> {
+    let mut buttons = vec!["PenTip".to_string()];
+    if pen_buttons > 0 {
+        buttons.push("PenPrimary".to_string());
+    }
+    if pen_buttons > 1 {
+        buttons.push("PenSecondary".to_string());
+    }
+    if eraser {
+        buttons.push("Eraser".to_string());
+    }
+    buttons.extend((0..tablet_buttons).map(|index| format!("Tablet{}", index)));
+    buttons.into_iter().map(Value::String).collect()
+}
+
+/// Literal start of a regex, along with whether anything was dropped
+fn regex_prefix(regex: &str) -> (String, bool) {
+    let regex = regex.strip_prefix('^').unwrap_or(regex);
+    let prefix: String = regex
+        .chars()
+        .take_while(|c| !"\\.[](){}*+?|$^".contains(*c))
+        .collect();
+    let lossy = prefix.len() != regex.trim_end_matches('$').len();
+    (prefix, lossy)
+}
+
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in input.bytes().filter(|c| *c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Pad report of Huion tablets, one bit per button starting at byte 4
+fn huion_pad_report(tablet_buttons: u64) -> Value {
+    let buttons: Vec<Value> = (0..tablet_buttons)
+        .map(|index| {
+            json!({
+                "byte": 4 + index / 8,
+                "bit": index % 8,
+                "button": format!("Tablet{}", index),
+            })
+        })
+        .collect();
+    json!({
+        "report_id": 8,
+        "discriminator": { "byte": 1, "value": 0xe0 },
+        "buttons": buttons,
+    })
+}
+
+fn import_otd(config: &Value) -> Result<Vec<Imported>, String> {
+    let name = config["Name"]
+        .as_str()
+        .ok_or("Not an OpenTabletDriver configuration, \"Name\" is missing.")?;
+    let specifications = &config["Specifications"];
+    let digitizer = &specifications["Digitizer"];
+    let identifiers = config["DigitizerIdentifiers"]
+        .as_array()
+        .filter(|identifiers| !identifiers.is_empty())
+        .ok_or("No DigitizerIdentifiers.")?;
+
+    let mut common = Map::new();
+    let mut untranslated = Vec::new();
+
+    for (field, key) in [("width", "MaxX"), ("height", "MaxY")] {
+        if digitizer[key].is_number() {
+            common.insert(field.to_string(), digitizer[key].clone());
+        } else {
+            untranslated.push(format!("Specifications.Digitizer.{}, missing", key));
+        }
+    }
+    let max_x = digitizer["MaxX"].as_f64();
+    let width = digitizer["Width"].as_f64().filter(|width| *width > 0.0);
+    if let (Some(max_x), Some(width)) = (max_x, width) {
+        let resolution = (max_x / (width / MM_PER_INCH as f64)).round() as u64;
+        common.insert("resolution".to_string(), json!(resolution));
+    }
+    if let Some(pressure_max) = specifications["Pen"]["MaxPressure"].as_u64() {
+        common.insert("pressure_max".to_string(), json!(pressure_max));
+    }
+    let pen_buttons = specifications["Pen"]["ButtonCount"].as_u64().unwrap_or(0);
+    let tablet_buttons = specifications["AuxiliaryButtons"]["ButtonCount"]
+        .as_u64()
+        .unwrap_or(0);
+    if pen_buttons > 2 {
+        untranslated.push(format!("Pen.ButtonCount beyond 2 ({})", pen_buttons));
+    }
+    if specifications["Wheel"].is_object() {
+        common.insert("wheel".to_string(), json!({ "kind": "Relative" }));
+    }
+    for unsupported in ["MouseButtons", "Touch"] {
+        if !specifications[unsupported].is_null() {
+            untranslated.push(format!("Specifications.{}", unsupported));
+        }
+    }
+    if config["AuxilaryDeviceIdentifiers"]
+        .as_array()
+        .is_some_and(|identifiers| !identifiers.is_empty())
+    {
+        untranslated.push("AuxilaryDeviceIdentifiers".to_string());
+    }
+    if config["Attributes"]
+        .as_object()
+        .is_some_and(|attributes| !attributes.is_empty())
+    {
+        untranslated.push("Attributes".to_string());
+    }
+
+    let mut imported = Vec::new();
+    for (index, identifier) in identifiers.iter().enumerate() {
+        let mut entry = common.clone();
+        let mut untranslated = untranslated.clone();
+        let vid = identifier["VendorID"].as_u64().ok_or("Missing VendorID.")?;
+        let pid = identifier["ProductID"]
+            .as_u64()
+            .ok_or("Missing ProductID.")?;
+        entry.insert("id".to_string(), json!(format!("{:04x}:{:04x}", vid, pid)));
+        if let Some(length) = identifier["InputReportLength"].as_u64() {
+            entry.insert("packet_length".to_string(), json!(length));
+        }
+
+        let parser = identifier["ReportParser"].as_str().unwrap_or_default();
+        let pad_buttons = if parser.contains(OTD_PARSER_HUION) {
+            entry.insert("extends".to_string(), json!(HUION_TEMPLATE));
+            let pad_buttons = tablet_buttons.min(HUION_PAD_BUTTONS);
+            if pad_buttons > 0 {
+                // the template only describes the pen report
+                entry.insert(
+                    "reports".to_string(),
+                    json!([{}, huion_pad_report(pad_buttons)]),
+                );
+            }
+            pad_buttons
+        } else if parser.contains(OTD_PARSER_XPPEN) {
+            entry.insert("extends".to_string(), json!(XPPEN_TEMPLATE));
+            tablet_buttons.min(XPPEN_PAD_BUTTONS)
+        } else {
+            if !parser.is_empty() {
+                untranslated.push(format!(
+                    "ReportParser {}, needs a hand written layout or a HID report descriptor",
+                    parser
+                ));
+            }
+            0
+        };
+        if tablet_buttons > pad_buttons {
+            untranslated.push(format!(
+                "AuxiliaryButtons.ButtonCount beyond {} ({}), no pad report layout for them",
+                pad_buttons, tablet_buttons
+            ));
+        }
+        entry.insert(
+            "button_available".to_string(),
+            Value::Array(button_names(pen_buttons, false, pad_buttons)),
+        );
+
+        if let Some(report) = identifier["OutputInitReport"]
+            .as_array()
+            .and_then(|reports| reports.first())
+            .and_then(Value::as_str)
+            .and_then(decode_base64)
+        {
+            entry.insert("init_report".to_string(), json!(report));
+        }
+        if !identifier["FeatureInitReport"].is_null() {
+            untranslated.push("FeatureInitReport".to_string());
+        }
+        if let Some(strings) = identifier["InitializationStrings"].as_array() {
+            if !strings.is_empty() && !parser.contains(OTD_PARSER_HUION) {
+                untranslated.push("InitializationStrings".to_string());
+            }
+        }
+        if let Some(device_strings) = identifier["DeviceStrings"].as_object() {
+            for (string_index, regex) in device_strings {
+                let regex = regex.as_str().unwrap_or_default();
+                if *string_index != FIRMWARE_STRING_INDEX.to_string() {
+                    untranslated.push(format!("DeviceStrings.{} {}", string_index, regex));
+                    continue;
+                }
+                let (prefix, lossy) = regex_prefix(regex);
+                entry.insert("match".to_string(), json!({ "firmware": prefix }));
+                if lossy {
+                    untranslated.push(format!(
+                        "DeviceStrings.{} {}, matched by the prefix {} only",
+                        string_index, regex, prefix
+                    ));
+                }
+            }
+        }
+
+        let name = if identifiers.len() > 1 {
+            format!("{}-{}", slug(name), index)
+        } else {
+            slug(name)
+        };
+        imported.push(Imported {
+            name,
+            entry,
+            untranslated,
+        });
+    }
+    Ok(imported)
+}
+
+fn parse_key_file(text: &str) -> KeyFile {
+    let mut key_file = KeyFile::new();
+    let mut section = String::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            section = name.to_string();
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            key_file
+                .entry(section.clone())
+                .or_default()
+                .insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    key_file
+}
+
+/// Values of a `;` separated list
+fn key_list(value: Option<&String>) -> Vec<&str> {
+    value
+        .map(|value| {
+            value
+                .split(';')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn import_libwacom(tablet: &KeyFile, styli: &[KeyFile]) -> Result<Vec<Imported>, String> {
+    let empty = BTreeMap::new();
+    let device = tablet
+        .get("Device")
+        .ok_or("Not a libwacom tablet file, [Device] is missing.")?;
+    let features = tablet.get("Features").unwrap_or(&empty);
+    let buttons = tablet.get("Buttons").unwrap_or(&empty);
+    let name = device.get("Name").ok_or("Missing Name.")?;
+    let is_true = |value: Option<&String>| value.is_some_and(|value| value == "true");
+
+    let mut common = Map::new();
+    let mut untranslated =
+        vec!["digitizer dimensions, read from the HID report descriptor".to_string()];
+
+    if let (Some(width), Some(height)) = (device.get("Width"), device.get("Height")) {
+        untranslated.push(format!("physical size {}x{} inches", width, height));
+    }
+
+    // styli are referenced by ID or by @group
+    let stylus_references = key_list(device.get("Styli"));
+    let matched_styli: Vec<&BTreeMap<String, String>> = styli
+        .iter()
+        .flat_map(|stylus_file| stylus_file.iter())
+        .filter(|(id, stylus)| {
+            stylus_references
+                .iter()
+                .any(|reference| match reference.strip_prefix('@') {
+                    Some(group) => stylus.get("Group").is_some_and(|value| value == group),
+                    None => reference.eq_ignore_ascii_case(id),
+                })
+        })
+        .map(|(_, stylus)| stylus)
+        .collect();
+    let (pen_buttons, eraser) = if matched_styli.is_empty() {
+        if is_true(features.get("Stylus")) {
+            untranslated.push(
+                "Styli, pass their .stylus files too, assuming two buttons and no eraser"
+                    .to_string(),
+            );
+        }
+        (2, false)
+    } else {
+        let pen_buttons = matched_styli
+            .iter()
+            .filter_map(|stylus| stylus.get("Buttons")?.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0);
+        let eraser = matched_styli.iter().any(|stylus| {
+            is_true(stylus.get("HasEraser"))
+                || stylus
+                    .get("EraserType")
+                    .is_some_and(|value| !value.is_empty() && value != "None")
+        });
+        (pen_buttons, eraser)
+    };
+
+    let tablet_buttons = ["Left", "Right", "Top", "Bottom"]
+        .into_iter()
+        .map(|side| key_list(buttons.get(side)).len() as u64)
+        .sum::<u64>();
+    common.insert(
+        "button_available".to_string(),
+        Value::Array(button_names(pen_buttons, eraser, tablet_buttons)),
+    );
+    if tablet_buttons > 0 {
+        untranslated.push(
+            "button positions, Tablet buttons are numbered left, right, top, bottom".to_string(),
+        );
+    }
+
+    if is_true(features.get("Ring")) {
+        common.insert(
+            "wheel".to_string(),
+            json!({ "kind": "Absolute", "positions": RING_POSITIONS }),
+        );
+        untranslated.push(format!(
+            "ring resolution, assuming {} positions",
+            RING_POSITIONS
+        ));
+    }
+    if is_true(features.get("Ring2")) {
+        untranslated.push("Ring2, only one wheel is supported".to_string());
+    }
+    if features
+        .get("NumStrips")
+        .is_some_and(|strips| strips != "0")
+    {
+        untranslated.push("NumStrips".to_string());
+    }
+    if is_true(features.get("Touch")) {
+        untranslated.push("Touch".to_string());
+    }
+
+    let mut usb_matches = Vec::new();
+    for device_match in key_list(device.get("DeviceMatch")) {
+        match device_match.split('|').collect::<Vec<_>>().as_slice() {
+            ["usb", vid, pid, rest @ ..] => usb_matches.push((*vid, *pid, rest.first().copied())),
+            _ => untranslated.push(format!(
+                "DeviceMatch {}, only USB is supported",
+                device_match
+            )),
+        }
+    }
+
+    let mut imported = Vec::new();
+    for (index, (vid, pid, product)) in usb_matches.iter().enumerate() {
+        let mut entry = common.clone();
+        entry.insert("id".to_string(), json!(format!("{}:{}", vid, pid)));
+        if let Some(product) = product {
+            entry.insert("match".to_string(), json!({ "product": product }));
+        }
+        let name = if usb_matches.len() > 1 {
+            format!("{}-{}", slug(name), index)
+        } else {
+            slug(name)
+        };
+        imported.push(Imported {
+            name,
+            entry,
+            untranslated: untranslated.clone(),
+        });
+    }
+    if imported.is_empty() {
+        Err("No USB DeviceMatch.".to_string())
+    } else {
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Map, Value};
+
+    use super::{decode_base64, import_libwacom, import_otd, parse_key_file, regex_prefix};
+    use crate::{
+        device_info::{builtin_raw_db, resolve_db, DbSource, DeviceInfo, RawDeviceInfo},
+        interfaces::{Button, PartialUpdate},
+        report::Decoder,
+    };
+
+    /// Resolves an imported entry the way a user device info file would be
+    fn resolve(name: &str, entry: &Map<String, Value>) -> DeviceInfo {
+        let mut raw_db = builtin_raw_db();
+        raw_db.insert(
+            (DbSource::User, name.to_string()),
+            RawDeviceInfo {
+                origin: name.to_string(),
+                value: Value::Object(entry.clone()),
+                source: DbSource::User,
+            },
+        );
+        let id = entry["id"].as_str().unwrap().parse().unwrap();
+        resolve_db(&raw_db)
+            .into_iter()
+            .find(|device_info| device_info.id == id)
+            .unwrap()
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(
+            decode_base64("ArAEAAAAAAAAAA=="),
+            Some(vec![0x02, 0xb0, 0x04, 0, 0, 0, 0, 0, 0, 0])
+        );
+        assert_eq!(decode_base64("+/8="), Some(vec![0xfb, 0xff]));
+        assert_eq!(decode_base64(""), Some(vec![]));
+        assert_eq!(decode_base64("AA-="), None);
+    }
+
+    #[test]
+    fn key_file() {
+        let key_file = parse_key_file(
+            "# comment\n[Device]\nName = Tablet\nDeviceMatch=usb|056a|0374;\n\n[Features]\nRing=true\nignored line\n",
+        );
+        assert_eq!(key_file.len(), 2);
+        assert_eq!(key_file["Device"]["Name"], "Tablet");
+        assert_eq!(key_file["Device"]["DeviceMatch"], "usb|056a|0374;");
+        assert_eq!(key_file["Features"].len(), 1);
+    }
+
+    #[test]
+    fn regex_prefixes() {
+        assert_eq!(
+            regex_prefix("^HUION_T21h$"),
+            ("HUION_T21h".to_string(), false)
+        );
+        assert_eq!(
+            regex_prefix("HUION_T21h"),
+            ("HUION_T21h".to_string(), false)
+        );
+        assert_eq!(
+            regex_prefix("^HUION_T21h_\\d{6}$"),
+            ("HUION_T21h_".to_string(), true)
+        );
+        assert_eq!(regex_prefix("^(A|B)"), (String::new(), true));
+    }
+
+    #[test]
+    fn otd_huion() {
+        let config = json!({
+            "Name": "Huion H640P",
+            "Specifications": {
+                "Digitizer": { "Width": 160.0, "Height": 100.0, "MaxX": 32000, "MaxY": 20000 },
+                "Pen": { "MaxPressure": 8191, "ButtonCount": 2 },
+                "AuxiliaryButtons": { "ButtonCount": 6 },
+                "MouseButtons": null,
+                "Touch": null
+            },
+            "DigitizerIdentifiers": [{
+                "VendorID": 9580,
+                "ProductID": 120,
+                "InputReportLength": 12,
+                "ReportParser": "OpenTabletDriver.Configurations.Parsers.UCLogic.UCLogicReportParser",
+                "FeatureInitReport": null,
+                "OutputInitReport": null,
+                "DeviceStrings": { "201": "^HUION_T21h_\\d{6}$" },
+                "InitializationStrings": [200]
+            }],
+            "AuxilaryDeviceIdentifiers": [],
+            "Attributes": {}
+        });
+        let imported = import_otd(&config).unwrap();
+        assert_eq!(imported.len(), 1);
+        let imported = &imported[0];
+        assert_eq!(imported.name, "huion-h640p");
+        assert_eq!(
+            imported.untranslated,
+            ["DeviceStrings.201 ^HUION_T21h_\\d{6}$, matched by the prefix HUION_T21h_ only"]
+        );
+
+        let device_info = resolve(&imported.name, &imported.entry);
+        assert_eq!(device_info.width, 32000.0);
+        assert_eq!(device_info.resolution, Some(5080.0));
+        assert_eq!(device_info.button_available.len(), 9);
+
+        // the second and third pad buttons held
+        let packet = [0x08, 0xe0, 0x01, 0x01, 0x06, 0x00, 0, 0, 0, 0, 0, 0];
+        let buttons: Vec<PartialUpdate> = (0..6)
+            .map(|index| {
+                PartialUpdate::Button(Button::Tablet(index), (1..3).contains(&index).into())
+            })
+            .collect();
+        assert_eq!(Decoder::new(&device_info).decode(&packet), buttons);
+    }
+
+    #[test]
+    fn otd_buttons_without_a_layout_are_dropped() {
+        let config = json!({
+            "Name": "Some Tablet",
+            "Specifications": {
+                "Digitizer": { "MaxX": 1000, "MaxY": 1000 },
+                "Pen": { "ButtonCount": 1 },
+                "AuxiliaryButtons": { "ButtonCount": 4 }
+            },
+            "DigitizerIdentifiers": [{ "VendorID": 1, "ProductID": 2, "ReportParser": "Other.Parser" }]
+        });
+        let imported = &import_otd(&config).unwrap()[0];
+        assert_eq!(
+            imported.entry["button_available"],
+            json!(["PenTip", "PenPrimary"])
+        );
+        assert!(imported.untranslated.contains(
+            &"AuxiliaryButtons.ButtonCount beyond 0 (4), no pad report layout for them".to_string()
+        ));
+    }
+
+    #[test]
+    fn libwacom() {
+        let tablet = parse_key_file(
+            "[Device]\nName=Wacom Intuos S\nDeviceMatch=usb|056a|0374;bluetooth|056a|0376;\nWidth=6\nHeight=4\nStyli=@intuos;\n\n[Features]\nStylus=true\nRing=true\nNumStrips=0\n\n[Buttons]\nTop=A;B;C;D\n",
+        );
+        let styli = [parse_key_file(
+            "[0x862]\nName=Pen\nGroup=intuos\nButtons=2\nEraserType=None\n\n[0x863]\nName=Other\nGroup=other\nButtons=3\n",
+        )];
+        let imported = import_libwacom(&tablet, &styli).unwrap();
+        assert_eq!(imported.len(), 1);
+        let imported = &imported[0];
+        assert_eq!(imported.name, "wacom-intuos-s");
+        assert_eq!(imported.entry["id"], "056a:0374");
+        assert_eq!(
+            imported.entry["button_available"],
+            json!([
+                "PenTip",
+                "PenPrimary",
+                "PenSecondary",
+                "Tablet0",
+                "Tablet1",
+                "Tablet2",
+                "Tablet3"
+            ])
+        );
+        assert_eq!(
+            imported.entry["wheel"],
+            json!({ "kind": "Absolute", "positions": 72 })
+        );
+        assert!(imported
+            .untranslated
+            .contains(&"DeviceMatch bluetooth|056a|0376, only USB is supported".to_string()));
+    }
+}
@@ -1,71 +1,206 @@
-use log::debug;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use log::{debug, info};
+use parking_lot::RwLock;
 
 use crate::{
-    interfaces::{Button, DeviceState, PartialUpdate, PenStatus},
+    bindings::{dispatch, Binding, Bindings},
+    config::{Config, DeviceConfig},
+    interfaces::{Button, ButtonState, DeviceState, PartialUpdate, PenStatus},
+    mapping::Mapping,
     platform::{VirtualInput, VirtualInputDevice},
+    pressure::TipDetector,
+    recovery::Recovery,
+    report::Decoder,
     tablet_device::TabletDevice,
-    utils::SelectBit,
+    wheel::WheelState,
 };
 
-pub(crate) fn device_handler(tablet: TabletDevice, device_state: DeviceState) {
+pub(crate) fn device_handler(
+    mut tablet: TabletDevice,
+    device_state: DeviceState,
+    config: Arc<RwLock<Config>>,
+    config_version: Arc<AtomicUsize>,
+    stop: Arc<AtomicBool>,
+) {
     // todo: parse input with scripting language
     //
-    // todo: listen for kill signal, [https://tokio.rs/tokio/topics/shutdown]
-    //
     // todo: async read?
 
     let mut vinput = VirtualInput::new(&tablet.device_info);
 
-    // todo: larger buffer
-    let mut buffer = [0; 12];
-    loop {
-        if let Some(_) = tablet.read(&mut buffer) {
-            match buffer[1] {
-                0b10000000 | 0b10000010 | 0b10000100 | 0b10000001 | 0b10000011 | 0b10000101
-                | 0b10000111 => {
-                    // pen => b10000000
-                    // pen primary button => b10000010
-                    // pen secondary button => b10000100
-                    // pen tip => b10000001
-                    let x: u32 = buffer[2] as u32 + ((buffer[3] as u32) << 8);
-                    let x = x as f32 / tablet.device_info.width;
-                    let y: u32 = buffer[4] as u32 + ((buffer[5] as u32) << 8);
-                    let y = y as f32 / tablet.device_info.height;
-
-                    let tilt_x = buffer[10] as i32;
-                    let tilt_y = buffer[11] as i32;
-
-                    let pressure = buffer[6] as i32;
+    let mut seen_config_version = config_version.load(Ordering::Acquire);
+    let mut device_config = config.read().device_config(&tablet.device_info.id);
+    let mut mapping = {
+        let config = config.read();
+        Mapping::new(
+            &tablet.device_info,
+            &device_config.area,
+            &device_config.orientation,
+            &config.screen,
+        )
+    };
 
-                    let pen_status = PenStatus {
-                        position: (x, y),
-                        pressure: Some(pressure),
-                        tilt: Some((tilt_x, tilt_y)),
-                    };
-                    vinput.submit_cursor(pen_status);
+    let mut decoder = Decoder::new(&tablet.device_info);
+    let mut tip_detector = TipDetector::default();
+    let mut wheel_state = WheelState::default();
+    let mut held_bindings = Bindings::new();
+    let mut tip_from_pressure = false;
 
-                    let mut device_state = device_state.write();
+    let mut recovery = Recovery::default();
+    let mut seen_generation = tablet.generation;
+    let mut buffer = vec![0; tablet.device_info.packet_length];
+    while !stop.load(Ordering::Relaxed) {
+        let length = match tablet.read(&mut buffer) {
+            Ok(length) => length,
+            Err(err) => {
+                if !recovery.handle(&mut tablet, &device_state, &stop, err) {
+                    break;
+                }
+                // a reset initializes the device again, which may report other parameters
+                if tablet.generation != seen_generation {
+                    seen_generation = tablet.generation;
+                    info!(
+                        "Device {} was reset, rebuilding its mapping and decoder.",
+                        tablet.device_info.id
+                    );
+                    mapping = Mapping::new(
+                        &tablet.device_info,
+                        &device_config.area,
+                        &device_config.orientation,
+                        &config.read().screen,
+                    );
+                    decoder = Decoder::new(&tablet.device_info);
+                    buffer.resize(tablet.device_info.packet_length, 0);
+                }
+                continue;
+            }
+        };
+        recovery.succeeded(&device_state);
 
-                    let button_state = buffer[1];
+        let latest_config_version = config_version.load(Ordering::Acquire);
+        if latest_config_version != seen_config_version {
+            seen_config_version = latest_config_version;
+            let config = config.read();
+            device_config = config.device_config(&tablet.device_info.id);
+            mapping.refresh(
+                &tablet.device_info,
+                &device_config.area,
+                &device_config.orientation,
+                &config.screen,
+            );
+        }
 
-                    let pen_tip = button_state.is_bit_set(7);
-                    device_state.update_button(Button::PenTip, pen_tip);
-                    // todo: vinput
+        for update in decoder.decode(&buffer[..length]) {
+            match update {
+                PartialUpdate::Pen(report) => {
+                    let (x, y) = report.position;
+                    tip_from_pressure = report.pressure.is_some();
+                    let pressure = report.pressure.map(|pressure| {
+                        let raw_pressure = pressure as f32 / tablet.device_info.pressure_max;
+                        let pen_tip = tip_detector.update(&device_config.pressure, raw_pressure);
+                        (pen_tip, device_config.pressure.apply(raw_pressure))
+                    });
 
-                    let pen_primary = button_state.is_bit_set(6);
-                    device_state.update_button(Button::PenPrimary, pen_primary);
+                    if let Some(position) = mapping.map(x as f32, y as f32) {
+                        let pen_status = PenStatus {
+                            position,
+                            pressure: pressure.map(
+                                |(pen_tip, pressure)| {
+                                    if pen_tip {
+                                        pressure
+                                    } else {
+                                        0.0
+                                    }
+                                },
+                            ),
+                            tilt: report.tilt,
+                        };
+                        vinput.submit_cursor(pen_status);
+                    }
 
-                    let pen_secondary = button_state.is_bit_set(5);
-                    device_state.update_button(Button::PenSecondary, pen_secondary);
+                    let mut device_state = device_state.write();
+                    device_state.pen_position = (x, y);
+                    if let Some((pen_tip, _)) = pressure {
+                        if device_state.update_button(Button::PenTip, pen_tip) {
+                            handle_button(
+                                &mut vinput,
+                                &mut wheel_state,
+                                &device_config,
+                                &mut held_bindings,
+                                Button::PenTip,
+                                pen_tip.into(),
+                            );
+                        }
+                    }
+                }
+                PartialUpdate::Button(Button::PenTip, _) if tip_from_pressure => {
+                    // tip state is derived from pressure when it is available
                 }
-                0b11110001 => {
-                    // wheel
+                PartialUpdate::Button(button, state) => {
+                    let pressed = state == ButtonState::Press;
+                    if device_state.write().update_button(button, pressed) {
+                        debug!("Button {} {:?}.", button, state);
+                        handle_button(
+                            &mut vinput,
+                            &mut wheel_state,
+                            &device_config,
+                            &mut held_bindings,
+                            button,
+                            state,
+                        );
+                    }
                 }
-                0b11100000 => {
-                    // button
+                PartialUpdate::Wheel(direction) => {
+                    debug!("Wheel turned {:?}.", direction);
+                    wheel_state.dispatch(&mut vinput, &device_config.wheel, &direction);
                 }
-                _ => {}
             }
-        };
+        }
+    }
+
+    // nothing should stay pressed once the virtual device goes away
+    let held: Vec<Button> = device_state.write().button_state.drain().collect();
+    for button in held {
+        debug!("Releasing held button {}.", button);
+        handle_button(
+            &mut vinput,
+            &mut wheel_state,
+            &device_config,
+            &mut held_bindings,
+            button,
+            ButtonState::Release,
+        );
+    }
+    info!("Handler of {} stopped.", tablet.device_info.id);
+}
+
+fn handle_button(
+    vinput: &mut VirtualInput,
+    wheel_state: &mut WheelState,
+    device_config: &DeviceConfig,
+    held_bindings: &mut Bindings,
+    button: Button,
+    state: ButtonState,
+) {
+    // the wheel button cycles wheel modes when there is more than one
+    if (button == Button::Wheel)
+        && (state == ButtonState::Press)
+        && (device_config.wheel.modes.len() > 1)
+    {
+        wheel_state.cycle(&device_config.wheel);
+        // so the release is swallowed as well
+        held_bindings.insert(button, Binding::Disabled);
+        return;
     }
+    dispatch(
+        vinput,
+        &device_config.bindings,
+        held_bindings,
+        button,
+        state,
+    );
 }
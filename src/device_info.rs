@@ -1,10 +1,17 @@
 use crate::{
+    device_match::DeviceMatch,
     interfaces::{Button, DeviceID},
+    protocol::Protocol,
+    report::ReportLayout,
     utils::LogExpect,
+    wheel::{deserialize_wheel, WheelInfo},
 };
 use log::{debug, error, info, warn};
 use serde::Deserialize;
+use serde_json::{Map, Value};
 use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Display,
     fs::{self, File},
     io::Read,
     path::PathBuf,
@@ -13,22 +20,102 @@ use std::{
 #[derive(Debug, Deserialize, Clone)]
 pub(crate) struct DeviceInfo {
     pub(crate) id: DeviceID,
+    #[serde(default, rename = "match")]
+    pub(crate) matcher: DeviceMatch,
+    #[serde(default)]
+    pub(crate) protocol: Protocol,
+    /// Interface to read reports from, the first one by default
+    #[serde(default)]
+    pub(crate) interface: Option<u8>,
+    /// Output report sent to the device before reading from it
+    #[serde(default)]
+    pub(crate) init_report: Option<Vec<u8>>,
+    /// May be left out when the device has a digitizer report descriptor
+    #[serde(default)]
     pub(crate) height: f32,
+    #[serde(default)]
     pub(crate) width: f32,
+    #[serde(default)]
     pub(crate) button_available: Vec<Button>,
-    pub(crate) wheel: bool,
+    #[serde(default, deserialize_with = "deserialize_wheel")]
+    pub(crate) wheel: Option<WheelInfo>,
+    /// Defaults to the span of `reports`
+    #[serde(default)]
     pub(crate) packet_length: usize,
+    /// Read from the HID report descriptor when empty
+    #[serde(default)]
+    pub(crate) reports: Vec<ReportLayout>,
+    #[serde(default = "default_pressure_max")]
+    pub(crate) pressure_max: f32,
+    /// Lines per inch
+    #[serde(default)]
+    pub(crate) resolution: Option<f32>,
+    #[serde(skip)]
+    pub(crate) source: DbSource,
+    /// File the entry was read from
+    #[serde(skip)]
+    pub(crate) origin: String,
+    /// Top level fields set by the entry rather than defaulted
+    #[serde(skip)]
+    pub(crate) provided: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum DbSource {
+    /// Embedded from the repository's `device_db/`
+    BuiltIn,
+    #[default]
+    User,
+    /// User entry replacing a built-in one
+    Override,
+}
+
+impl Display for DbSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BuiltIn => f.pad("built-in"),
+            Self::User => f.pad("user"),
+            Self::Override => f.pad("user override"),
+        }
+    }
+}
+
+mod builtin {
+    include!(concat!(env!("OUT_DIR"), "/builtin_db.rs"));
+}
+
+impl DeviceInfo {
+    /// Fills in a missing `packet_length` and rejects layouts the decoder can't handle
+    pub(crate) fn validate(&mut self) -> Result<(), String> {
+        for (index, layout) in self.reports.iter().enumerate() {
+            layout
+                .validate()
+                .map_err(|err| format!("report {}: {}", index, err))?;
+        }
+        if self.packet_length == 0 {
+            self.packet_length = self
+                .reports
+                .iter()
+                .map(ReportLayout::span)
+                .max()
+                .unwrap_or(0);
+        }
+        Ok(())
+    }
+}
+
+fn default_pressure_max() -> f32 {
+    u8::MAX as f32
 }
 
 pub(crate) type DeviceDB = Vec<DeviceInfo>;
 
 pub(crate) fn load_db(path: &PathBuf) -> DeviceDB {
+    let mut raw_db = builtin_raw_db();
     if !path.exists() {
         warn!("Device database does not exist, creating.");
         fs::create_dir_all(path).log_expect("Failed to create device database directory.");
-        info!("Empty device database created.");
-        // fetch device database
-        Vec::new()
+        info!("Empty device database created, using built-in entries only.");
     } else if !path.is_dir() {
         error!(
             "Provided device database path {} is not a directory!",
@@ -36,19 +123,105 @@ pub(crate) fn load_db(path: &PathBuf) -> DeviceDB {
         );
         panic!("Exiting.")
     } else {
-        let mut db = Vec::new();
-        read_db_dir(path, &mut db);
-        info!("Loaded {} device info files.", db.len());
-        db
+        read_db_dir(path, &mut raw_db);
+    }
+    let db = prefer_user_entries(resolve_db(&raw_db));
+    info!("Loaded {} device info files.", db.len());
+    db
+}
+
+pub(crate) fn builtin_raw_db() -> RawDeviceDB {
+    let mut raw_db = RawDeviceDB::new();
+    for (file_name, content) in builtin::BUILTIN_DB {
+        let name = file_name.trim_end_matches(".json").to_string();
+        match serde_json::from_str::<Value>(content) {
+            Ok(value) => {
+                raw_db.insert(
+                    (DbSource::BuiltIn, name),
+                    RawDeviceInfo {
+                        origin: format!("<built-in>/{}", file_name),
+                        value,
+                        source: DbSource::BuiltIn,
+                    },
+                );
+            }
+            Err(err) => error!("Built-in {} is invalid: {}, skipping.", file_name, err),
+        }
     }
+    raw_db
+}
+
+/// ID and matcher, which together identify an entry, like duplicates in `db check`
+type EntryKey = (DeviceID, DeviceMatch);
+
+fn entry_key(device_info: &DeviceInfo) -> EntryKey {
+    (device_info.id, device_info.matcher.clone())
 }
 
-fn read_db_dir(path: &PathBuf, db: &mut DeviceDB) {
+/// Drops built-in entries that also have a user entry with the same ID and matcher
+fn prefer_user_entries(db: DeviceDB) -> DeviceDB {
+    let user_keys: HashSet<EntryKey> = db
+        .iter()
+        .filter(|device_info| device_info.source != DbSource::BuiltIn)
+        .map(entry_key)
+        .collect();
+    let builtin_keys: HashSet<EntryKey> = db
+        .iter()
+        .filter(|device_info| device_info.source == DbSource::BuiltIn)
+        .map(entry_key)
+        .collect();
+    db.into_iter()
+        .filter(|device_info| {
+            let overridden = (device_info.source == DbSource::BuiltIn)
+                && user_keys.contains(&entry_key(device_info));
+            if overridden {
+                debug!("Built-in entry {} is overridden.", device_info.origin);
+            }
+            !overridden
+        })
+        .map(|mut device_info| {
+            if (device_info.source == DbSource::User)
+                && builtin_keys.contains(&entry_key(&device_info))
+            {
+                device_info.source = DbSource::Override;
+            }
+            device_info
+        })
+        .collect()
+}
+
+pub(crate) fn print_db(db: &DeviceDB) {
+    let mut db: Vec<&DeviceInfo> = db.iter().collect();
+    db.sort_by_key(|device_info| (device_info.id.vid, device_info.id.pid));
+    for device_info in db {
+        println!(
+            "{}  {:<14} {}",
+            device_info.id, device_info.source, device_info.origin
+        );
+    }
+}
+
+/// A device info file before its `extends` chain is resolved
+pub(crate) struct RawDeviceInfo {
+    /// Where the file came from, for diagnostics
+    pub(crate) origin: String,
+    pub(crate) value: Value,
+    pub(crate) source: DbSource,
+}
+
+/// Marks keys whose array extends the base array instead of being merged into it
+const APPEND_SUFFIX: char = '+';
+
+/// Source and file stem, built-in and user files live in separate namespaces
+pub(crate) type RawKey = (DbSource, String);
+
+/// Raw files by source and file stem, the stem is what `extends` refers to
+pub(crate) type RawDeviceDB = BTreeMap<RawKey, RawDeviceInfo>;
+
+fn read_db_dir(path: &PathBuf, raw_db: &mut RawDeviceDB) {
     debug!("Reading {}", path.display());
     if path.is_file() {
-        if let Some(result) = parse_device_info(path) {
-            db.push(result);
-        }
+        read_db_file(path, raw_db);
     } else {
         for entry in path
             .read_dir()
@@ -58,11 +231,9 @@ fn read_db_dir(path: &PathBuf, db: &mut DeviceDB) {
                 Ok(entry) => {
                     let path = entry.path();
                     if path.is_dir() {
-                        read_db_dir(&path, db);
+                        read_db_dir(&path, raw_db);
                     } else {
-                        if let Some(device_info) = parse_device_info(&path) {
-                            db.push(device_info);
-                        }
+                        read_db_file(&path, raw_db);
                     }
                 }
                 Err(err) => {
@@ -74,27 +245,260 @@ fn read_db_dir(path: &PathBuf, db: &mut DeviceDB) {
     }
 }
 
-fn parse_device_info(path: &PathBuf) -> Option<DeviceInfo> {
+fn read_db_file(path: &PathBuf, raw_db: &mut RawDeviceDB) {
+    let Some(name) = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+    else {
+        return;
+    };
     let file = File::open(path);
     if let Ok(mut file) = file {
         let mut content = Vec::new();
-        if let Ok(_) = file.read_to_end(&mut content) {
-            if let Ok(device_info) = serde_json::from_slice::<DeviceInfo>(&content) {
-                debug!("Loaded config for device {}", device_info.id);
-                Some(device_info)
+        if file.read_to_end(&mut content).is_ok() {
+            if let Ok(value @ Value::Object(_)) = serde_json::from_slice::<Value>(&content) {
+                let origin = path.display().to_string();
+                // whether a user entry overrides a built-in one is decided by ID and matcher
+                let key = (DbSource::User, name);
+                if let Some(existing) = raw_db.get(&key) {
+                    warn!(
+                        "Ignoring {}, it has the same name as {}.",
+                        origin, existing.origin
+                    );
+                    return;
+                }
+                raw_db.insert(
+                    key,
+                    RawDeviceInfo {
+                        origin,
+                        value,
+                        source: DbSource::User,
+                    },
+                );
             } else {
                 info!(
-                    "{} is not a valid device info file, skipping.",
+                    "{} is not a valid device info file, skipping. Run `rablet db check` for details.",
                     path.display()
                 );
-                None
             }
         } else {
             error!("Failed to read file {}, skipping.", path.display());
-            None
         }
     } else {
         error!("Failed to read file {}, skipping.", path.display());
+    }
+}
+
+/// Files without an `id` are templates, only used through `extends`
+pub(crate) fn resolve_db(raw_db: &RawDeviceDB) -> DeviceDB {
+    let mut db = Vec::new();
+    for (key, raw) in raw_db {
+        let name = &key.1;
+        if raw.value.get("id").is_none() {
+            continue;
+        }
+        let resolved = match resolve(raw_db, key, &mut Vec::new()) {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                error!("Failed to resolve {}: {}, skipping.", raw.origin, err);
+                continue;
+            }
+        };
+        for (field, origin) in &resolved.origins {
+            debug!("{}: {} from {}", name, field, origin);
+        }
+        let provided = resolved.fields.keys().cloned().collect();
+        match serde_json::from_value::<DeviceInfo>(Value::Object(resolved.fields)) {
+            Ok(mut device_info) => {
+                device_info.provided = provided;
+                if let Err(err) = device_info.validate() {
+                    info!(
+                        "{} is not a valid device info file: {}, skipping.",
+                        raw.origin, err
+                    );
+                    continue;
+                }
+                debug!("Loaded config for device {}", device_info.id);
+                device_info.source = raw.source;
+                device_info.origin = raw.origin.clone();
+                db.push(device_info);
+            }
+            Err(err) => {
+                info!(
+                    "{} is not a valid device info file: {}, skipping.",
+                    raw.origin, err
+                );
+            }
+        }
+    }
+    db
+}
+
+pub(crate) struct ResolvedDeviceInfo {
+    pub(crate) fields: Map<String, Value>,
+    /// Origin of each top level field
+    pub(crate) origins: BTreeMap<String, String>,
+}
+
+/// Key of the file `extends` refers to, built-in templates win so user files never change
+/// what built-in entries resolve to
+fn base_key(raw_db: &RawDeviceDB, base: &str, source: DbSource) -> Option<RawKey> {
+    let builtin = (DbSource::BuiltIn, base.to_string());
+    let user = (DbSource::User, base.to_string());
+    if raw_db.contains_key(&builtin) {
+        Some(builtin)
+    } else if (source != DbSource::BuiltIn) && raw_db.contains_key(&user) {
+        Some(user)
+    } else {
         None
     }
 }
+
+pub(crate) fn resolve(
+    raw_db: &RawDeviceDB,
+    key: &RawKey,
+    chain: &mut Vec<RawKey>,
+) -> Result<ResolvedDeviceInfo, String> {
+    if chain.contains(key) {
+        chain.push(key.clone());
+        let names: Vec<&str> = chain.iter().map(|(_, name)| name.as_str()).collect();
+        return Err(format!("inheritance cycle {}", names.join(" -> ")));
+    }
+    let raw = raw_db
+        .get(key)
+        .ok_or_else(|| format!("unknown base \"{}\"", key.1))?;
+    let Value::Object(mut fields) = raw.value.clone() else {
+        return Err(format!("{} is not an object", raw.origin));
+    };
+    chain.push(key.clone());
+
+    let mut resolved = match fields.remove("extends") {
+        None => ResolvedDeviceInfo {
+            fields: Map::new(),
+            origins: BTreeMap::new(),
+        },
+        Some(Value::String(base)) => {
+            let base_key = base_key(raw_db, &base, key.0)
+                .ok_or_else(|| format!("unknown base \"{}\"", base))?;
+            resolve(raw_db, &base_key, chain)?
+        }
+        Some(_) => return Err("\"extends\" must be a file name".to_string()),
+    };
+    for (field, value) in fields {
+        let name = field.strip_suffix(APPEND_SUFFIX).unwrap_or(&field);
+        resolved
+            .origins
+            .insert(name.to_string(), raw.origin.clone());
+        merge_field(&mut resolved.fields, field, value);
+    }
+    chain.pop();
+    Ok(resolved)
+}
+
+/// Objects are merged key by key and non-empty arrays of objects element by element,
+/// anything else is replaced
+fn merge(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Object(base), Value::Object(value)) => {
+            for (key, value) in value {
+                merge_field(base, key, value);
+            }
+        }
+        (Value::Array(base), Value::Array(value))
+            if !value.is_empty() && is_object_array(base) && is_object_array(&value) =>
+        {
+            let mut value = value.into_iter();
+            for (base, value) in base.iter_mut().zip(&mut value) {
+                merge(base, value);
+            }
+            base.extend(value);
+        }
+        (base, value) => *base = value,
+    }
+}
+
+/// A key ending in `+` appends its array to the base array, e.g. `"buttons+"`
+fn merge_field(base: &mut Map<String, Value>, key: String, value: Value) {
+    if let Some(key) = key.strip_suffix(APPEND_SUFFIX) {
+        match (base.get_mut(key), value) {
+            (Some(Value::Array(base)), Value::Array(value)) => base.extend(value),
+            (_, value) => {
+                base.insert(key.to_string(), value);
+            }
+        }
+        return;
+    }
+    match base.get_mut(&key) {
+        Some(base) => merge(base, value),
+        None => {
+            base.insert(key, value);
+        }
+    }
+}
+
+fn is_object_array(values: &[Value]) -> bool {
+    values.iter().all(Value::is_object)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{builtin_raw_db, merge, prefer_user_entries, resolve_db, DbSource, RawDeviceInfo};
+
+    #[test]
+    fn user_file_named_like_a_builtin_template_stays_separate() {
+        let mut raw_db = builtin_raw_db();
+        raw_db.insert(
+            (DbSource::User, "xppen-deco-common".to_string()),
+            RawDeviceInfo {
+                origin: "xppen-deco-common.json".to_string(),
+                value: json!({ "id": "1234:5678", "width": 100, "height": 100 }),
+                source: DbSource::User,
+            },
+        );
+        let db = prefer_user_entries(resolve_db(&raw_db));
+        let deco = db
+            .iter()
+            .find(|device_info| device_info.id.to_string() == "28bd:0905")
+            .unwrap();
+        assert_eq!(deco.source, DbSource::BuiltIn);
+        assert_eq!(deco.reports.len(), 2);
+        let user = db
+            .iter()
+            .find(|device_info| device_info.id.to_string() == "1234:5678")
+            .unwrap();
+        assert_eq!(user.source, DbSource::User);
+    }
+
+    #[test]
+    fn merge_arrays() {
+        let mut base = json!({
+            "button_available": ["PenTip", "PenPrimary"],
+            "reports": [
+                { "report_id": 2, "buttons": [{ "button": "Tablet0" }] },
+                { "report_id": 3 }
+            ]
+        });
+        merge(
+            &mut base,
+            json!({
+                "button_available": ["Eraser"],
+                "reports": [{ "buttons+": [{ "button": "Wheel" }] }, {}, { "report_id": 4 }]
+            }),
+        );
+        assert_eq!(
+            base,
+            json!({
+                "button_available": ["Eraser"],
+                "reports": [
+                    { "report_id": 2, "buttons": [{ "button": "Tablet0" }, { "button": "Wheel" }] },
+                    { "report_id": 3 },
+                    { "report_id": 4 }
+                ]
+            })
+        );
+        merge(&mut base, json!({ "reports": [] }));
+        assert_eq!(base["reports"], json!([]));
+    }
+}
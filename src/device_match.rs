@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+use log::{debug, warn};
+use rusb::{
+    Device, DeviceDescriptor, DeviceHandle, Direction, GlobalContext, Recipient, RequestType,
+};
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    device_info::{DeviceDB, DeviceInfo},
+    protocol::{DESCRIPTOR_TIMEOUT, REQUEST_GET_DESCRIPTOR},
+};
+
+/// Huion and Gaomon report their firmware name here
+pub(crate) const FIRMWARE_STRING_INDEX: u8 = 201;
+const STRING_TIMEOUT: Duration = Duration::from_millis(500);
+const DESCRIPTOR_TYPE_DEVICE: u16 = 0x01;
+const DEVICE_DESCRIPTOR_LENGTH: usize = 18;
+/// Offset of `bcdDevice` in the device descriptor
+const BCD_DEVICE_OFFSET: usize = 12;
+
+/// Tells apart models sharing a VID:PID, every given matcher has to match
+#[derive(Debug, Deserialize, Clone, Default, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub(crate) struct DeviceMatch {
+    /// String matchers are prefixes, e.g. `HUION_T21j` for `HUION_T21j_221221`
+    pub(crate) manufacturer: Option<String>,
+    pub(crate) product: Option<String>,
+    pub(crate) serial: Option<String>,
+    pub(crate) firmware: Option<String>,
+    /// `bcdDevice`, either a number or a hex string like `"0x0100"`
+    #[serde(deserialize_with = "deserialize_bcd")]
+    pub(crate) bcd_device: Option<u16>,
+    pub(crate) interfaces: Option<u8>,
+}
+
+fn deserialize_bcd<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u16>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BcdField {
+        Number(u16),
+        Hex(String),
+    }
+
+    match Option::<BcdField>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(BcdField::Number(value)) => Ok(Some(value)),
+        Some(BcdField::Hex(value)) => {
+            let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+            u16::from_str_radix(digits, 16)
+                .map(Some)
+                .map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+/// What a connected device tells about itself, missing strings are `None`
+#[derive(Debug, Default)]
+pub(crate) struct DeviceProperties {
+    pub(crate) manufacturer: Option<String>,
+    pub(crate) product: Option<String>,
+    pub(crate) serial: Option<String>,
+    pub(crate) firmware: Option<String>,
+    pub(crate) bcd_device: u16,
+    pub(crate) interfaces: Option<u8>,
+}
+
+impl DeviceMatch {
+    /// Number of matchers given, more specific entries win
+    pub(crate) fn specificity(&self) -> usize {
+        [
+            self.manufacturer.is_some(),
+            self.product.is_some(),
+            self.serial.is_some(),
+            self.firmware.is_some(),
+            self.bcd_device.is_some(),
+            self.interfaces.is_some(),
+        ]
+        .into_iter()
+        .filter(|given| *given)
+        .count()
+    }
+
+    pub(crate) fn matches(&self, properties: &DeviceProperties) -> bool {
+        let string_matches = |matcher: &Option<String>, value: &Option<String>| match matcher {
+            Some(prefix) => value
+                .as_ref()
+                .is_some_and(|value| value.starts_with(prefix.as_str())),
+            None => true,
+        };
+        string_matches(&self.manufacturer, &properties.manufacturer)
+            && string_matches(&self.product, &properties.product)
+            && string_matches(&self.serial, &properties.serial)
+            && string_matches(&self.firmware, &properties.firmware)
+            && self
+                .bcd_device
+                .is_none_or(|bcd_device| bcd_device == properties.bcd_device)
+            && self
+                .interfaces
+                .is_none_or(|interfaces| Some(interfaces) == properties.interfaces)
+    }
+}
+
+/// Entries for the VID:PID of `descriptor`, the device only needs to be opened if there are any
+pub(crate) fn candidates<'a>(
+    device_db: &'a DeviceDB,
+    descriptor: &DeviceDescriptor,
+) -> Vec<&'a DeviceInfo> {
+    device_db
+        .iter()
+        .filter(|device_info| {
+            (device_info.id.vid == descriptor.vendor_id())
+                && (device_info.id.pid == descriptor.product_id())
+        })
+        .collect()
+}
+
+pub(crate) fn read_properties(
+    device: &Device<GlobalContext>,
+    handle: &DeviceHandle<GlobalContext>,
+    descriptor: &DeviceDescriptor,
+) -> DeviceProperties {
+    let language = handle
+        .read_languages(STRING_TIMEOUT)
+        .ok()
+        .and_then(|languages| languages.first().copied());
+    let read_string = |index: Option<u8>| {
+        let language = language?;
+        handle
+            .read_string_descriptor(language, index?, STRING_TIMEOUT)
+            .ok()
+    };
+    let properties = DeviceProperties {
+        manufacturer: read_string(descriptor.manufacturer_string_index()),
+        product: read_string(descriptor.product_string_index()),
+        serial: read_string(descriptor.serial_number_string_index()),
+        firmware: read_string(Some(FIRMWARE_STRING_INDEX)),
+        bcd_device: read_bcd_device(handle, descriptor),
+        interfaces: device
+            .active_config_descriptor()
+            .ok()
+            .map(|config| config.num_interfaces()),
+    };
+    debug!(
+        "Properties of {:04x}:{:04x}: {:?}",
+        descriptor.vendor_id(),
+        descriptor.product_id(),
+        properties
+    );
+    properties
+}
+
+/// rusb only exposes `bcdDevice` as a decimal `Version`, which turns nibbles above 9 into
+/// carries, so the raw field is read from the device descriptor instead
+fn read_bcd_device(handle: &DeviceHandle<GlobalContext>, descriptor: &DeviceDescriptor) -> u16 {
+    let mut buffer = [0; DEVICE_DESCRIPTOR_LENGTH];
+    match handle.read_control(
+        rusb::request_type(Direction::In, RequestType::Standard, Recipient::Device),
+        REQUEST_GET_DESCRIPTOR,
+        DESCRIPTOR_TYPE_DEVICE << 8,
+        0,
+        &mut buffer,
+        DESCRIPTOR_TIMEOUT,
+    ) {
+        Ok(length) if length == DEVICE_DESCRIPTOR_LENGTH => {
+            u16::from_le_bytes([buffer[BCD_DEVICE_OFFSET], buffer[BCD_DEVICE_OFFSET + 1]])
+        }
+        result => {
+            // only exact while every nibble is a decimal digit
+            debug!(
+                "Failed to read the device descriptor of {:04x}:{:04x} ({:?}), using the parsed version.",
+                descriptor.vendor_id(),
+                descriptor.product_id(),
+                result
+            );
+            let version = descriptor.device_version();
+            ((version.major() as u16 / 10) << 12)
+                | ((version.major() as u16 % 10) << 8)
+                | ((version.minor() as u16) << 4)
+                | version.sub_minor() as u16
+        }
+    }
+}
+
+/// Picks the most specific matching entry, the first one listed on ties
+pub(crate) fn select<'a>(
+    candidates: &[&'a DeviceInfo],
+    properties: &DeviceProperties,
+) -> Option<&'a DeviceInfo> {
+    let mut matching: Vec<&DeviceInfo> = candidates
+        .iter()
+        .copied()
+        .filter(|device_info| device_info.matcher.matches(properties))
+        .collect();
+    // stable, so ties keep their database order
+    matching.sort_by_key(|device_info| std::cmp::Reverse(device_info.matcher.specificity()));
+    if let [first, second, ..] = matching.as_slice() {
+        if first.matcher.specificity() == second.matcher.specificity() {
+            warn!(
+                "Several device database entries for {} match equally well, using the first one.",
+                first.id
+            );
+        }
+    }
+    matching.first().copied()
+}
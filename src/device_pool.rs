@@ -1,63 +1,451 @@
-use std::sync::Arc;
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
 
 use crate::{
+    config::Config,
     device_handler::device_handler,
-    interfaces::{new_device_state, GlobalState},
+    device_info::DeviceInfo,
+    device_match,
+    interfaces::{new_device_state, DeviceState, GlobalState},
     tablet_device::TabletDevice,
     utils::LogExpect,
 };
-use log::{error, info, warn};
+use futures::future::join_all;
+use log::{debug, error, info, warn};
+use parking_lot::RwLock;
+use rusb::{
+    Device, DeviceDescriptor, DeviceHandle, GlobalContext, Hotplug, HotplugBuilder, UsbContext,
+};
+#[cfg(unix)]
+use tokio::signal::unix::SignalKind;
+use tokio::{sync::mpsc, task::JoinHandle};
+
+/// Used when libusb has no hotplug support
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// udev may not have applied permissions yet when a device arrives
+const OPEN_ATTEMPTS: u32 = 5;
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(200);
+/// Time handlers get to release held buttons and their devices
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+enum PoolEvent {
+    Arrived(Device<GlobalContext>),
+    Left(Device<GlobalContext>),
+    /// A probe finished, `None` if the device was skipped
+    Started(DeviceKey, Option<RunningDevice>),
+    /// A handler exited, because it was stopped, gave up or panicked
+    Stopped(DeviceKey, DeviceState),
+}
 
-//use rusb::hotplug after initial thread spawning
+/// Bus number and address, unique among connected devices
+type DeviceKey = (u8, u8);
+
+fn device_key(device: &Device<GlobalContext>) -> DeviceKey {
+    (device.bus_number(), device.address())
+}
+
+struct RunningDevice {
+    device_state: DeviceState,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+/// What handlers need from the pool, cloned into every probe
+#[derive(Clone)]
+struct HandlerContext {
+    config: Arc<RwLock<Config>>,
+    config_version: Arc<AtomicUsize>,
+    sender: mpsc::UnboundedSender<PoolEvent>,
+}
+
+/// Tells the pool that a handler exited, also when it panicked
+struct StoppedNotifier {
+    key: DeviceKey,
+    device_state: DeviceState,
+    sender: mpsc::UnboundedSender<PoolEvent>,
+}
+
+impl Drop for StoppedNotifier {
+    fn drop(&mut self) {
+        let _ = self
+            .sender
+            .send(PoolEvent::Stopped(self.key, Arc::clone(&self.device_state)));
+    }
+}
+
+struct HotplugForwarder {
+    sender: mpsc::UnboundedSender<PoolEvent>,
+}
+
+impl Hotplug<GlobalContext> for HotplugForwarder {
+    fn device_arrived(&mut self, device: Device<GlobalContext>) {
+        let _ = self.sender.send(PoolEvent::Arrived(device));
+    }
+
+    fn device_left(&mut self, device: Device<GlobalContext>) {
+        let _ = self.sender.send(PoolEvent::Left(device));
+    }
+}
 
 pub(crate) fn spawn_device_pool_thread(mut global_state: GlobalState) {
-    tokio::runtime::Builder::new_current_thread()
+    let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
-        .unwrap()
-        .block_on(async move {
-            let mut parser_handles = Vec::new();
-            'outer: for device in rusb::devices()
-                .log_expect("Failed to get USB device list, check permissions.")
-                .iter()
-            {
-                if let Ok(device_desc) = device.device_descriptor() {
-                    for existing_config in &global_state.device_db {
-                        if (device_desc.vendor_id() == existing_config.id.vid)
-                            & (device_desc.product_id() == existing_config.id.pid)
-                        {
-                            info!(
-                                "Found valid device {:04x}:{:04x}, opening.",
-                                device_desc.vendor_id(),
-                                device_desc.product_id()
-                            );
-                            if let Ok(device_handle) = device.open() {
-                                let tablet = TabletDevice::new(device_handle, existing_config);
-                                let device_state = new_device_state(existing_config);
-                                global_state.devices.push(Arc::clone(&device_state));
-                                let handle = tokio::task::spawn_blocking(|| {
-                                    device_handler(tablet, device_state)
-                                });
-                                parser_handles.push(handle);
-                                continue 'outer;
-                            } else {
-                                error!(
-                                    "Failed to open matched device {:04x}:{:04x}.",
-                                    device_desc.vendor_id(),
-                                    device_desc.product_id()
-                                );
-                                break;
-                            }
-                        }
+        .unwrap();
+    runtime.block_on(async move {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        watch_devices(sender.clone());
+        let context = HandlerContext {
+            config: Arc::clone(&global_state.config),
+            config_version: Arc::clone(&global_state.config_version),
+            sender,
+        };
+
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+        let mut reload = reload_signal();
+
+        let mut running: HashMap<DeviceKey, RunningDevice> = HashMap::new();
+        // devices being probed, removed again if they leave meanwhile
+        let mut starting: HashSet<DeviceKey> = HashSet::new();
+        // handlers of disconnected devices that may still be releasing buttons
+        let mut stopping: Vec<JoinHandle<()>> = Vec::new();
+        loop {
+            let event = tokio::select! {
+                _ = &mut shutdown => break,
+                _ = reload_requested(&mut reload) => {
+                    reload_config(&global_state);
+                    continue;
+                }
+                event = receiver.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+            };
+            match event {
+                PoolEvent::Arrived(device) => {
+                    let key = device_key(&device);
+                    if running.contains_key(&key) || starting.contains(&key) {
+                        continue;
+                    }
+                    let Some(candidates) = device_candidates(&global_state, &device) else {
+                        continue;
+                    };
+                    starting.insert(key);
+                    // probing blocks for up to a few seconds, the loop keeps handling signals
+                    let context = context.clone();
+                    tokio::spawn(async move {
+                        let started = start_device(key, device, candidates, &context).await;
+                        let _ = context.sender.send(PoolEvent::Started(key, started));
+                    });
+                }
+                PoolEvent::Started(key, started) => {
+                    let Some(running_device) = started else {
+                        starting.remove(&key);
+                        continue;
+                    };
+                    if starting.remove(&key) {
+                        global_state
+                            .devices
+                            .push(Arc::clone(&running_device.device_state));
+                        running.insert(key, running_device);
+                    } else {
+                        info!("Device left while it was being opened, stopping its handler.");
+                        running_device.stop.store(true, Ordering::Relaxed);
+                        stopping.push(wait_for_handler(running_device));
+                    }
+                }
+                PoolEvent::Left(device) => {
+                    let key = device_key(&device);
+                    starting.remove(&key);
+                    let Some(running_device) = running.remove(&key) else {
+                        continue;
+                    };
+                    info!(
+                        "Device {} disconnected, stopping its handler.",
+                        running_device.device_state.read().id
+                    );
+                    running_device.stop.store(true, Ordering::Relaxed);
+                    retire(&mut global_state, &mut stopping, running_device);
+                }
+                PoolEvent::Stopped(key, device_state) => {
+                    // the key may already belong to another device
+                    let Entry::Occupied(entry) = running.entry(key) else {
+                        continue;
+                    };
+                    if !Arc::ptr_eq(&entry.get().device_state, &device_state) {
+                        continue;
                     }
-                } else {
-                    warn!(
-                        "Failed to get information of device at {}-{}, skipping.",
-                        device.address(),
-                        device.port_number()
+                    let running_device = entry.remove();
+                    info!(
+                        "Handler of {} exited, reconnect the device to restart it.",
+                        device_state.read().id
                     );
+                    retire(&mut global_state, &mut stopping, running_device);
+                }
+            }
+        }
+
+        info!("Shutting down, stopping {} device handlers.", running.len());
+        for running_device in running.values() {
+            running_device.stop.store(true, Ordering::Relaxed);
+        }
+        let handles = running
+            .into_values()
+            .map(|running_device| running_device.handle)
+            .chain(stopping);
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, join_all(handles))
+            .await
+            .is_err()
+        {
+            warn!(
+                "Device handlers did not stop within {} seconds, exiting anyway.",
+                SHUTDOWN_TIMEOUT.as_secs()
+            );
+        }
+    });
+    // handlers stuck in a blocking call are abandoned
+    runtime.shutdown_timeout(Duration::ZERO);
+}
+
+/// Forgets a device whose handler is stopping and logs how the handler ended
+fn retire(
+    global_state: &mut GlobalState,
+    stopping: &mut Vec<JoinHandle<()>>,
+    running_device: RunningDevice,
+) {
+    global_state
+        .devices
+        .retain(|device_state| !Arc::ptr_eq(device_state, &running_device.device_state));
+    stopping.retain(|handle| !handle.is_finished());
+    stopping.push(wait_for_handler(running_device));
+}
+
+fn wait_for_handler(running_device: RunningDevice) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let id = running_device.device_state.read().id;
+        if let Err(err) = running_device.handle.await {
+            error!("Handler of {} failed: {}.", id, err);
+        }
+    })
+}
+
+/// Resolves on SIGINT, or SIGTERM on unix
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(SignalKind::terminate())
+            .log_expect("Failed to listen for SIGTERM.");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(unix)]
+type ReloadSignal = tokio::signal::unix::Signal;
+#[cfg(not(unix))]
+type ReloadSignal = ();
+
+/// SIGHUP reloads the config
+fn reload_signal() -> ReloadSignal {
+    #[cfg(unix)]
+    {
+        tokio::signal::unix::signal(SignalKind::hangup()).log_expect("Failed to listen for SIGHUP.")
+    }
+}
+
+async fn reload_requested(signal: &mut ReloadSignal) {
+    #[cfg(unix)]
+    {
+        signal.recv().await;
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = signal;
+        futures::future::pending::<()>().await;
+    }
+}
+
+fn reload_config(global_state: &GlobalState) {
+    match Config::read_config(&global_state.config_path) {
+        Ok(config) => {
+            *global_state.config.write() = config;
+            global_state.config_version.fetch_add(1, Ordering::Release);
+            info!("Reloaded config {}.", global_state.config_path.display());
+        }
+        Err(err) => error!(
+            "Failed to reload config {}: {}, keeping the current one.",
+            global_state.config_path.display(),
+            err
+        ),
+    }
+}
+
+/// Forwards arriving and leaving devices to `sender`, starting with those already connected
+fn watch_devices(sender: mpsc::UnboundedSender<PoolEvent>) {
+    if rusb::has_hotplug() {
+        thread::spawn(move || {
+            let registration = HotplugBuilder::new().enumerate(true).register(
+                GlobalContext::default(),
+                Box::new(HotplugForwarder { sender }),
+            );
+            let _registration = registration.log_expect("Failed to register hotplug callback.");
+            debug!("Watching for devices through libusb hotplug.");
+            loop {
+                if let Err(err) = GlobalContext::default().handle_events(None) {
+                    error!("Failed to handle USB events: {}.", err);
                 }
             }
-            futures::future::join_all(parser_handles).await;
         });
+    } else {
+        info!(
+            "libusb has no hotplug support, polling for devices every {} seconds.",
+            POLL_INTERVAL.as_secs()
+        );
+        thread::spawn(move || {
+            let mut known: HashMap<DeviceKey, Device<GlobalContext>> = HashMap::new();
+            loop {
+                let devices =
+                    rusb::devices().log_expect("Failed to get USB device list, check permissions.");
+                let present: HashSet<DeviceKey> =
+                    devices.iter().map(|device| device_key(&device)).collect();
+                for (key, device) in &known {
+                    if !present.contains(key) {
+                        let _ = sender.send(PoolEvent::Left(device.clone()));
+                    }
+                }
+                known.retain(|key, _| present.contains(key));
+                for device in devices.iter() {
+                    if let Entry::Vacant(entry) = known.entry(device_key(&device)) {
+                        entry.insert(device.clone());
+                        if sender.send(PoolEvent::Arrived(device)).is_err() {
+                            return;
+                        }
+                    }
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+    }
+}
+
+/// Blocks while retrying
+fn open_device(device: &Device<GlobalContext>) -> Option<DeviceHandle<GlobalContext>> {
+    for attempt in 1..=OPEN_ATTEMPTS {
+        match device.open() {
+            Ok(handle) => return Some(handle),
+            Err(err) if attempt == OPEN_ATTEMPTS => {
+                error!(
+                    "Failed to open device at {}-{}: {}.",
+                    device.bus_number(),
+                    device.address(),
+                    err
+                );
+            }
+            Err(_) => thread::sleep(OPEN_RETRY_DELAY),
+        }
+    }
+    None
+}
+
+/// Opens `device` and picks its entry among `candidates`, blocks on USB transfers
+fn probe_device(
+    device: &Device<GlobalContext>,
+    device_desc: &DeviceDescriptor,
+    candidates: &[DeviceInfo],
+) -> Option<(DeviceHandle<GlobalContext>, DeviceInfo)> {
+    let device_handle = open_device(device)?;
+    let properties = device_match::read_properties(device, &device_handle, device_desc);
+    let candidates: Vec<&DeviceInfo> = candidates.iter().collect();
+    let Some(device_info) = device_match::select(&candidates, &properties) else {
+        warn!(
+            "No device database entry for {:04x}:{:04x} matches {:?}, skipping.",
+            device_desc.vendor_id(),
+            device_desc.product_id(),
+            properties
+        );
+        return None;
+    };
+    Some((device_handle, device_info.clone()))
+}
+
+/// Database entries for the VID:PID of `device`, `None` if there are none
+fn device_candidates(
+    global_state: &GlobalState,
+    device: &Device<GlobalContext>,
+) -> Option<Vec<DeviceInfo>> {
+    let Ok(device_desc) = device.device_descriptor() else {
+        warn!(
+            "Failed to get information of device at {}-{}, skipping.",
+            device.bus_number(),
+            device.address()
+        );
+        return None;
+    };
+    let candidates: Vec<DeviceInfo> =
+        device_match::candidates(&global_state.device_db, &device_desc)
+            .into_iter()
+            .cloned()
+            .collect();
+    (!candidates.is_empty()).then_some(candidates)
+}
+
+/// Opens `device` if one of `candidates` matches and spawns its handler
+async fn start_device(
+    key: DeviceKey,
+    device: Device<GlobalContext>,
+    candidates: Vec<DeviceInfo>,
+    context: &HandlerContext,
+) -> Option<RunningDevice> {
+    let device_desc = device.device_descriptor().ok()?;
+    let (vid, pid) = (device_desc.vendor_id(), device_desc.product_id());
+    info!("Found valid device {:04x}:{:04x}, opening.", vid, pid);
+    let probe =
+        tokio::task::spawn_blocking(move || probe_device(&device, &device_desc, &candidates));
+    let (device_handle, device_info) = match probe.await {
+        Ok(probed) => probed?,
+        Err(err) => {
+            error!("Probing device {:04x}:{:04x} failed: {}.", vid, pid, err);
+            return None;
+        }
+    };
+
+    let device_state = new_device_state(&device_info);
+    let stop = Arc::new(AtomicBool::new(false));
+    let config = Arc::clone(&context.config);
+    let config_version = Arc::clone(&context.config_version);
+    let notifier = StoppedNotifier {
+        key,
+        device_state: Arc::clone(&device_state),
+        sender: context.sender.clone(),
+    };
+    let handle = {
+        let device_state = Arc::clone(&device_state);
+        let stop = Arc::clone(&stop);
+        tokio::task::spawn_blocking(move || {
+            let _notifier = notifier;
+            match TabletDevice::new(device_handle, &device_info) {
+                Ok(tablet) => device_handler(tablet, device_state, config, config_version, stop),
+                Err(err) => error!("{}, not starting its handler.", err),
+            }
+        })
+    };
+    Some(RunningDevice {
+        device_state,
+        stop,
+        handle,
+    })
 }
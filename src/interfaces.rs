@@ -5,24 +5,39 @@ use crate::{
     device_info::{DeviceDB, DeviceInfo},
 };
 use parking_lot::RwLock;
-use serde::Deserialize;
-use std::{collections::HashSet, fmt::Display, sync::Arc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    path::PathBuf,
+    str::FromStr,
+    sync::{atomic::AtomicUsize, Arc},
+};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub(crate) enum PartialUpdate {
-    Pen(PenStatus),
+    Pen(PenReport),
     Button(Button, ButtonState),
     Wheel(WheelDirection),
 }
 
+/// Pen values in raw device units
+#[derive(Debug, PartialEq)]
+pub(crate) struct PenReport {
+    pub(crate) position: (u32, u32),
+    pub(crate) pressure: Option<u32>,
+    pub(crate) tilt: Option<(i32, i32)>,
+}
+
 #[derive(Debug)]
 pub(crate) struct PenStatus {
     pub(crate) position: (f32, f32),
     pub(crate) tilt: Option<(i32, i32)>,
-    pub(crate) pressure: Option<i32>,
+    pub(crate) pressure: Option<f32>,
 }
 
-#[derive(Deserialize, Hash, PartialEq, Eq, Clone, Debug, Copy)]
+#[derive(Serialize, Deserialize, Hash, PartialEq, Eq, Clone, Debug, Copy)]
+#[serde(try_from = "String", into = "String")]
 pub(crate) enum Button {
     PenPrimary,
     PenSecondary,
@@ -32,7 +47,45 @@ pub(crate) enum Button {
     Wheel,
 }
 
-#[derive(Debug, Clone)]
+impl TryFrom<String> for Button {
+    type Error = &'static str;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "PenPrimary" => Ok(Self::PenPrimary),
+            "PenSecondary" => Ok(Self::PenSecondary),
+            "Eraser" => Ok(Self::Eraser),
+            "PenTip" => Ok(Self::PenTip),
+            "Wheel" => Ok(Self::Wheel),
+            _ => {
+                let index = value.strip_prefix("Tablet").ok_or("Invalid button.")?;
+                let index = index.parse().map_err(|_| "Invalid tablet button index.")?;
+                Ok(Self::Tablet(index))
+            }
+        }
+    }
+}
+
+impl From<Button> for String {
+    fn from(value: Button) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for Button {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PenPrimary => write!(f, "PenPrimary"),
+            Self::PenSecondary => write!(f, "PenSecondary"),
+            Self::Eraser => write!(f, "Eraser"),
+            Self::PenTip => write!(f, "PenTip"),
+            Self::Tablet(index) => write!(f, "Tablet{index}"),
+            Self::Wheel => write!(f, "Wheel"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub(crate) enum ButtonState {
     Press,
     Release,
@@ -57,32 +110,70 @@ impl ButtonState {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) enum WheelDirection {
     Clockwise,
     CounterClockwise,
 }
 
-#[derive(Clone, Debug, Deserialize, Copy)]
-#[serde(try_from = "String")]
+#[derive(Clone, Debug, Serialize, Deserialize, Copy, Hash, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
 pub(crate) struct DeviceID {
     pub(crate) vid: u16,
     pub(crate) pid: u16,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ParseDeviceIdError {
+    Format(String),
+    Vid(String),
+    Pid(String),
+}
+
+impl Display for ParseDeviceIdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Format(value) => write!(
+                f,
+                "Invalid device ID \"{value}\", expected vid:pid in hex, e.g. 256c:006d."
+            ),
+            Self::Vid(vid) => write!(f, "Invalid VID \"{vid}\", expected 4 hex digits."),
+            Self::Pid(pid) => write!(f, "Invalid PID \"{pid}\", expected 4 hex digits."),
+        }
+    }
+}
+
+impl std::error::Error for ParseDeviceIdError {}
+
+impl FromStr for DeviceID {
+    type Err = ParseDeviceIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s.to_string())
+    }
+}
+
 impl TryFrom<String> for DeviceID {
-    type Error = &'static str;
+    type Error = ParseDeviceIdError;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let mut segments = value.split(":");
-        let vid = segments.next().ok_or("Invalid format.")?;
-        let vid = u16::from_str_radix(&vid, 16).map_err(|_| "Invalid VID.")?;
-        let pid = segments.next().ok_or("Invalid format.")?;
-        let pid = u16::from_str_radix(&pid, 16).map_err(|_| "Invalid PID.")?;
+        let (vid, pid) = value
+            .split_once(':')
+            .ok_or_else(|| ParseDeviceIdError::Format(value.clone()))?;
+        let vid =
+            u16::from_str_radix(vid, 16).map_err(|_| ParseDeviceIdError::Vid(vid.to_string()))?;
+        let pid =
+            u16::from_str_radix(pid, 16).map_err(|_| ParseDeviceIdError::Pid(pid.to_string()))?;
         Ok(Self { vid, pid })
     }
 }
 
+impl From<DeviceID> for String {
+    fn from(value: DeviceID) -> Self {
+        value.to_string()
+    }
+}
+
 impl Display for DeviceID {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:04x}:{:04x}", self.vid, self.pid);
@@ -96,16 +187,42 @@ pub(crate) struct DeviceStateInner {
     pub(crate) pen_position: (u32, u32),
     pub(crate) button_state: HashSet<Button>,
     pub(crate) tilt: Option<u32>,
+    pub(crate) connection: ConnectionState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ConnectionState {
+    Connected,
+    /// Recovering from a read error, `attempt` counts from 1
+    Recovering {
+        attempt: u32,
+    },
+    /// The handler has given up on the device
+    Disconnected,
+}
+
+impl Display for ConnectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connected => write!(f, "connected"),
+            Self::Recovering { attempt } => write!(f, "recovering (attempt {})", attempt),
+            Self::Disconnected => write!(f, "disconnected"),
+        }
+    }
 }
 
 impl DeviceStateInner {
-    pub(crate) fn update_button(&mut self, button: Button, pressed: bool) {
+    /// Returns whether the state of the button has changed
+    pub(crate) fn update_button(&mut self, button: Button, pressed: bool) -> bool {
         if self.button_state.contains(&button) ^ pressed {
             if pressed {
                 self.button_state.insert(button);
             } else {
                 self.button_state.remove(&button);
             }
+            true
+        } else {
+            false
         }
     }
 }
@@ -119,6 +236,7 @@ pub(crate) fn new_device_state(device_info: &DeviceInfo) -> DeviceState {
         pen_position: (0, 0),
         button_state,
         tilt: None,
+        connection: ConnectionState::Connected,
     }))
 }
 
@@ -126,13 +244,22 @@ pub(crate) fn new_device_state(device_info: &DeviceInfo) -> DeviceState {
 pub(crate) struct GlobalState {
     pub(crate) devices: Vec<DeviceState>,
     pub(crate) config: Arc<RwLock<Config>>,
+    /// Bumped whenever `config` is replaced, so handlers only re-read it after a reload
+    pub(crate) config_version: Arc<AtomicUsize>,
+    pub(crate) config_path: PathBuf,
     pub(crate) device_db: DeviceDB,
 }
 
-pub(crate) fn new_global_state(config: Config, device_db: DeviceDB) -> GlobalState {
+pub(crate) fn new_global_state(
+    config: Config,
+    config_path: PathBuf,
+    device_db: DeviceDB,
+) -> GlobalState {
     GlobalState {
         devices: Vec::new(),
         config: Arc::new(RwLock::new(config)),
+        config_version: Arc::new(AtomicUsize::new(0)),
+        config_path,
         device_db,
     }
 }
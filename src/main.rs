@@ -1,38 +1,137 @@
 //todo: tracing/logging
-use cli_options::parse_cli_options;
+use cli_options::{parse_cli_options, CliOptions};
 use config::Config;
-use device_info::load_db;
+#[cfg(unix)]
+use daemon::{daemonize, print_status, stop_daemon};
+use db_check::check_db;
+use db_import::import_files;
+use device_info::{load_db, print_db};
 use device_pool::spawn_device_pool_thread;
 use interfaces::new_global_state;
+use log::debug;
+use pressure::print_curve_table;
+use std::io::{self, IsTerminal};
 use udev::install_udev_rules;
 use utils::print_huion_device_input;
+use wizard::run_wizard;
 
+mod bindings;
 mod cli_options;
 mod config;
+#[cfg(unix)]
+mod daemon;
+mod db_check;
+mod db_import;
 mod device_handler;
 mod device_info;
+mod device_match;
 mod device_pool;
 mod interfaces;
+mod mapping;
 mod platform;
+mod pressure;
+mod protocol;
+mod recovery;
+mod report;
 mod tablet_device;
 // todo: linux only
 mod udev;
 mod utils;
+mod wheel;
+mod wizard;
 
 fn main() {
-    pretty_env_logger::init();
-
     let cli_options = parse_cli_options();
-    let config = Config::load_config(&cli_options.get_config());
-    let device_db = load_db(&cli_options.get_device_db());
-    let global_state = new_global_state(config, device_db);
 
-    match cli_options.command {
-        cli_options::Command::Run | cli_options::Command::Deamon => {
-            spawn_device_pool_thread(global_state);
+    // before the logger, which decides on colors depending on whether stderr is a terminal,
+    // the guard removes the pidfile once main returns
+    #[cfg(unix)]
+    let mut pidfile = match cli_options.command {
+        cli_options::Command::Deamon => Some(daemonize(
+            cli_options.get_pidfile(),
+            cli_options.get_log_file(),
+        )),
+        _ => None,
+    };
+    pretty_env_logger::init();
+    debug!("Read command-line options: {:#?}", cli_options);
+
+    match &cli_options.command {
+        cli_options::Command::Run => run(&cli_options, io::stdin().is_terminal(), || {}),
+        #[cfg(unix)]
+        cli_options::Command::Deamon => run(&cli_options, false, || {
+            if let Some(pidfile) = &mut pidfile {
+                pidfile.notify_ready();
+            }
+        }),
+        #[cfg(unix)]
+        cli_options::Command::Stop => {
+            if !stop_daemon(cli_options.get_pidfile()) {
+                std::process::exit(1);
+            }
+        }
+        #[cfg(unix)]
+        cli_options::Command::Status => {
+            if !print_status(cli_options.get_pidfile()) {
+                std::process::exit(1);
+            }
         }
         cli_options::Command::Install => {
             install_udev_rules();
         }
+        cli_options::Command::Curve { device, steps } => {
+            let config = Config::load_config(cli_options.get_config(), io::stdin().is_terminal());
+            println!("Pressure curve of {}:", device);
+            print_curve_table(&config.device_config(device).pressure, *steps);
+        }
+        cli_options::Command::Db { command } => match command {
+            cli_options::DbCommand::List => print_db(&load_db(cli_options.get_device_db())),
+            cli_options::DbCommand::Check { path } => {
+                if !check_db(path.as_ref().unwrap_or(cli_options.get_device_db())) {
+                    std::process::exit(1);
+                }
+            }
+            cli_options::DbCommand::Import {
+                files,
+                output,
+                force,
+            } => {
+                let output = output.as_ref().unwrap_or(cli_options.get_device_db());
+                if !import_files(files, output, *force) {
+                    std::process::exit(1);
+                }
+            }
+            cli_options::DbCommand::New {
+                device,
+                protocol,
+                interface,
+                record,
+                replay,
+                output,
+            } => {
+                let output = output.as_ref().unwrap_or(cli_options.get_device_db());
+                let success = run_wizard(
+                    *device,
+                    *protocol,
+                    *interface,
+                    replay.as_deref(),
+                    record.as_deref(),
+                    output,
+                );
+                if !success {
+                    std::process::exit(1);
+                }
+            }
+        },
     }
 }
+
+/// Runs the device pool until shut down, `interactive` allows prompting on stdin.
+/// `ready` is called once the config and device database are loaded.
+fn run(cli_options: &CliOptions, interactive: bool, ready: impl FnOnce()) {
+    let config = Config::load_config(cli_options.get_config(), interactive);
+    let device_db = load_db(cli_options.get_device_db());
+    ready();
+    let global_state = new_global_state(config, cli_options.get_config().clone(), device_db);
+    spawn_device_pool_thread(global_state);
+}
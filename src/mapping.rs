@@ -0,0 +1,470 @@
+use crate::{config::ScreenSize, device_info::DeviceInfo};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+pub(crate) const MM_PER_INCH: f32 = 25.4;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub(crate) struct AreaConfig {
+    /// Active tablet area, the whole tablet is used when absent
+    pub(crate) tablet: Option<TabletArea>,
+    /// Target screen region in pixels, the whole screen is used when absent
+    pub(crate) output: Option<OutputArea>,
+    /// What to do with points outside of the active tablet area
+    pub(crate) outside: OutsideBehaviour,
+    pub(crate) mode: MappingMode,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum MappingMode {
+    /// Stretch the tablet area to fill the output area
+    #[default]
+    Stretch,
+    /// Trim the tablet area to match the aspect ratio of the output area
+    Proportional(Anchor),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum Anchor {
+    #[default]
+    Center,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct TabletArea {
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+    #[serde(default)]
+    pub(crate) unit: AreaUnit,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum AreaUnit {
+    #[default]
+    Device,
+    Millimeter,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct OutputArea {
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum OutsideBehaviour {
+    #[default]
+    Clamp,
+    Ignore,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub(crate) struct Orientation {
+    pub(crate) rotation: Rotation,
+    pub(crate) invert_x: bool,
+    pub(crate) invert_y: bool,
+}
+
+/// Clockwise rotation of the tablet
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(try_from = "u16", into = "u16")]
+pub(crate) enum Rotation {
+    #[default]
+    None,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+impl TryFrom<u16> for Rotation {
+    type Error = &'static str;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::None),
+            90 => Ok(Self::Quarter),
+            180 => Ok(Self::Half),
+            270 => Ok(Self::ThreeQuarters),
+            _ => Err("Rotation must be one of 0, 90, 180 or 270."),
+        }
+    }
+}
+
+impl From<Rotation> for u16 {
+    fn from(value: Rotation) -> Self {
+        match value {
+            Rotation::None => 0,
+            Rotation::Quarter => 90,
+            Rotation::Half => 180,
+            Rotation::ThreeQuarters => 270,
+        }
+    }
+}
+
+impl AreaConfig {
+    /// Rejects empty areas, which would divide by zero when mapping
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if let Some(tablet) = &self.tablet {
+            validate_size("tablet area", tablet.width, tablet.height)?;
+        }
+        if let Some(output) = &self.output {
+            validate_size("output area", output.width, output.height)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn validate_size(name: &str, width: f32, height: f32) -> Result<(), String> {
+    for (dimension, value) in [("width", width), ("height", height)] {
+        if !(value.is_finite() && (value > 0.0)) {
+            return Err(format!(
+                "{} {} is {}, it must be positive",
+                name, dimension, value
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl Orientation {
+    /// Tablet dimensions as seen by the user
+    fn dimensions(&self, device_info: &DeviceInfo) -> (f32, f32) {
+        match self.rotation {
+            Rotation::None | Rotation::Half => (device_info.width, device_info.height),
+            Rotation::Quarter | Rotation::ThreeQuarters => (device_info.height, device_info.width),
+        }
+    }
+
+    /// Transforms raw device coordinates into the user's frame
+    fn apply(&self, (width, height): (f32, f32), x: f32, y: f32) -> (f32, f32) {
+        // width and height are already rotated
+        let (x, y) = match self.rotation {
+            Rotation::None => (x, y),
+            Rotation::Quarter => (width - y, x),
+            Rotation::Half => (width - x, height - y),
+            Rotation::ThreeQuarters => (y, height - x),
+        };
+        let x = if self.invert_x { width - x } else { x };
+        let y = if self.invert_y { height - y } else { y };
+        (x, y)
+    }
+}
+
+/// Rectangle as (x, y, width, height)
+type Rect = (f32, f32, f32, f32);
+
+/// Maps raw device coordinates onto the normalized virtual device range
+#[derive(Debug, Clone)]
+pub(crate) struct Mapping {
+    area: AreaConfig,
+    orientation: Orientation,
+    screen: ScreenSize,
+    dimensions: (f32, f32),
+    tablet: Rect,
+    output: Rect,
+}
+
+impl Mapping {
+    pub(crate) fn new(
+        device_info: &DeviceInfo,
+        area: &AreaConfig,
+        orientation: &Orientation,
+        screen: &ScreenSize,
+    ) -> Mapping {
+        let dimensions = orientation.dimensions(device_info);
+
+        let tablet = match &area.tablet {
+            Some(tablet_area) => {
+                let scale = match (tablet_area.unit, device_info.resolution) {
+                    (AreaUnit::Device, _) => 1.0,
+                    (AreaUnit::Millimeter, Some(resolution)) => resolution / MM_PER_INCH,
+                    (AreaUnit::Millimeter, None) => {
+                        warn!(
+                            "Device {} has no known resolution, treating area as device units.",
+                            device_info.id
+                        );
+                        1.0
+                    }
+                };
+                (
+                    tablet_area.x * scale,
+                    tablet_area.y * scale,
+                    tablet_area.width * scale,
+                    tablet_area.height * scale,
+                )
+            }
+            None => (0.0, 0.0, dimensions.0, dimensions.1),
+        };
+
+        let output = match &area.output {
+            Some(output_area) => (
+                output_area.x / screen.width,
+                output_area.y / screen.height,
+                output_area.width / screen.width,
+                output_area.height / screen.height,
+            ),
+            None => (0.0, 0.0, 1.0, 1.0),
+        };
+
+        let tablet = match area.mode {
+            MappingMode::Stretch => tablet,
+            MappingMode::Proportional(anchor) => {
+                // compare in pixels, since the normalized output rect is not square
+                let output_ratio = (output.2 * screen.width) / (output.3 * screen.height);
+                trim_to_ratio(tablet, output_ratio, anchor)
+            }
+        };
+
+        Mapping {
+            area: area.clone(),
+            orientation: orientation.clone(),
+            screen: screen.clone(),
+            dimensions,
+            tablet,
+            output,
+        }
+    }
+
+    /// Recomputes the mapping if the configuration has changed
+    pub(crate) fn refresh(
+        &mut self,
+        device_info: &DeviceInfo,
+        area: &AreaConfig,
+        orientation: &Orientation,
+        screen: &ScreenSize,
+    ) {
+        if (self.area != *area) || (self.orientation != *orientation) || (self.screen != *screen) {
+            debug!(
+                "Mapping configuration of {} changed, remapping.",
+                device_info.id
+            );
+            *self = Mapping::new(device_info, area, orientation, screen);
+        }
+    }
+
+    /// Returns `None` if the point should be dropped
+    pub(crate) fn map(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let (x, y) = self.orientation.apply(self.dimensions, x, y);
+
+        let (tablet_x, tablet_y, tablet_width, tablet_height) = self.tablet;
+        let relative_x = (x - tablet_x) / tablet_width;
+        let relative_y = (y - tablet_y) / tablet_height;
+
+        let inside = (0.0..=1.0).contains(&relative_x) && (0.0..=1.0).contains(&relative_y);
+        let (relative_x, relative_y) = match (inside, self.area.outside) {
+            (true, _) => (relative_x, relative_y),
+            (false, OutsideBehaviour::Clamp) => {
+                (relative_x.clamp(0.0, 1.0), relative_y.clamp(0.0, 1.0))
+            }
+            (false, OutsideBehaviour::Ignore) => return None,
+        };
+
+        let (output_x, output_y, output_width, output_height) = self.output;
+        Some((
+            (output_x + relative_x * output_width).clamp(0.0, 1.0),
+            (output_y + relative_y * output_height).clamp(0.0, 1.0),
+        ))
+    }
+}
+
+fn trim_to_ratio(rect: Rect, ratio: f32, anchor: Anchor) -> Rect {
+    let (x, y, width, height) = rect;
+    let (trimmed_width, trimmed_height) = if width / height > ratio {
+        (height * ratio, height)
+    } else {
+        (width, width / ratio)
+    };
+    let spare_width = width - trimmed_width;
+    let spare_height = height - trimmed_height;
+    let (offset_x, offset_y) = match anchor {
+        Anchor::Center => (spare_width / 2.0, spare_height / 2.0),
+        Anchor::TopLeft => (0.0, 0.0),
+        Anchor::TopRight => (spare_width, 0.0),
+        Anchor::BottomLeft => (0.0, spare_height),
+        Anchor::BottomRight => (spare_width, spare_height),
+    };
+    (x + offset_x, y + offset_y, trimmed_width, trimmed_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        Anchor, AreaConfig, AreaUnit, Mapping, MappingMode, Orientation, OutputArea,
+        OutsideBehaviour, Rotation, TabletArea,
+    };
+    use crate::{config::ScreenSize, device_info::DeviceInfo};
+
+    fn device_info() -> DeviceInfo {
+        serde_json::from_value(json!({
+            "id": "256c:006d",
+            "width": 2000,
+            "height": 1000,
+            "resolution": 254
+        }))
+        .unwrap()
+    }
+
+    fn screen() -> ScreenSize {
+        ScreenSize {
+            width: 1000.0,
+            height: 500.0,
+        }
+    }
+
+    fn new_mapping(area: &AreaConfig, orientation: &Orientation) -> Mapping {
+        Mapping::new(&device_info(), area, orientation, &screen())
+    }
+
+    fn tablet_area(x: f32, y: f32, width: f32, height: f32) -> Option<TabletArea> {
+        Some(TabletArea {
+            x,
+            y,
+            width,
+            height,
+            unit: AreaUnit::Device,
+        })
+    }
+
+    #[test]
+    fn rotation() {
+        for (rotation, origin, point) in [
+            (Rotation::None, (0.0, 0.0), (0.25, 0.25)),
+            (Rotation::Quarter, (1.0, 0.0), (0.75, 0.25)),
+            (Rotation::Half, (1.0, 1.0), (0.75, 0.75)),
+            (Rotation::ThreeQuarters, (0.0, 1.0), (0.25, 0.75)),
+        ] {
+            let orientation = Orientation {
+                rotation,
+                ..Default::default()
+            };
+            let mapping = new_mapping(&AreaConfig::default(), &orientation);
+            assert_eq!(mapping.map(0.0, 0.0), Some(origin), "{:?}", rotation);
+            assert_eq!(mapping.map(500.0, 250.0), Some(point), "{:?}", rotation);
+        }
+    }
+
+    #[test]
+    fn rotation_swaps_dimensions() {
+        let orientation = Orientation {
+            rotation: Rotation::Quarter,
+            ..Default::default()
+        };
+        let area = AreaConfig {
+            // the upper half of the tablet as seen by the user, in the rotated frame
+            tablet: tablet_area(0.0, 0.0, 1000.0, 1000.0),
+            outside: OutsideBehaviour::Ignore,
+            ..Default::default()
+        };
+        let mapping = new_mapping(&area, &orientation);
+        assert_eq!(mapping.map(0.0, 1000.0), Some((0.0, 0.0)));
+        assert_eq!(mapping.map(1000.0, 0.0), Some((1.0, 1.0)));
+        assert_eq!(mapping.map(1500.0, 0.0), None);
+    }
+
+    #[test]
+    fn inversion() {
+        let orientation = Orientation {
+            invert_x: true,
+            ..Default::default()
+        };
+        let mapping = new_mapping(&AreaConfig::default(), &orientation);
+        assert_eq!(mapping.map(500.0, 250.0), Some((0.75, 0.25)));
+
+        let orientation = Orientation {
+            rotation: Rotation::Half,
+            invert_x: true,
+            invert_y: true,
+        };
+        let mapping = new_mapping(&AreaConfig::default(), &orientation);
+        assert_eq!(mapping.map(500.0, 250.0), Some((0.25, 0.25)));
+    }
+
+    #[test]
+    fn area_clamping() {
+        let mut area = AreaConfig {
+            tablet: tablet_area(500.0, 250.0, 1000.0, 500.0),
+            ..Default::default()
+        };
+        let clamping = new_mapping(&area, &Orientation::default());
+        assert_eq!(clamping.map(1000.0, 500.0), Some((0.5, 0.5)));
+        assert_eq!(clamping.map(0.0, 0.0), Some((0.0, 0.0)));
+        assert_eq!(clamping.map(2000.0, 600.0), Some((1.0, 0.7)));
+
+        area.outside = OutsideBehaviour::Ignore;
+        let ignoring = new_mapping(&area, &Orientation::default());
+        assert_eq!(ignoring.map(1000.0, 500.0), Some((0.5, 0.5)));
+        assert_eq!(ignoring.map(0.0, 0.0), None);
+        assert_eq!(ignoring.map(2000.0, 600.0), None);
+    }
+
+    #[test]
+    fn area_units_and_output() {
+        let area = AreaConfig {
+            // 254 lpi is 10 device units per millimeter
+            tablet: Some(TabletArea {
+                x: 50.0,
+                y: 25.0,
+                width: 100.0,
+                height: 50.0,
+                unit: AreaUnit::Millimeter,
+            }),
+            output: Some(OutputArea {
+                x: 500.0,
+                y: 0.0,
+                width: 500.0,
+                height: 500.0,
+            }),
+            ..Default::default()
+        };
+        let mapping = new_mapping(&area, &Orientation::default());
+        assert_eq!(mapping.map(500.0, 250.0), Some((0.5, 0.0)));
+        assert_eq!(mapping.map(1000.0, 500.0), Some((0.75, 0.5)));
+    }
+
+    #[test]
+    fn proportional() {
+        let area = AreaConfig {
+            output: Some(OutputArea {
+                x: 0.0,
+                y: 0.0,
+                width: 500.0,
+                height: 500.0,
+            }),
+            mode: MappingMode::Proportional(Anchor::Center),
+            ..Default::default()
+        };
+        // a square output trims the 2:1 tablet to its middle square
+        let mapping = new_mapping(&area, &Orientation::default());
+        assert_eq!(mapping.map(500.0, 0.0), Some((0.0, 0.0)));
+        assert_eq!(mapping.map(1000.0, 500.0), Some((0.25, 0.5)));
+        assert_eq!(mapping.map(0.0, 0.0), Some((0.0, 0.0)));
+    }
+
+    #[test]
+    fn empty_areas_are_rejected() {
+        for (width, height) in [(0.0, 100.0), (100.0, -1.0), (f32::NAN, 100.0)] {
+            let area = AreaConfig {
+                tablet: tablet_area(0.0, 0.0, width, height),
+                ..Default::default()
+            };
+            assert!(area.validate().is_err(), "{}x{}", width, height);
+        }
+        let area = AreaConfig {
+            tablet: tablet_area(10.0, 10.0, 100.0, 100.0),
+            ..Default::default()
+        };
+        assert_eq!(area.validate(), Ok(()));
+    }
+}
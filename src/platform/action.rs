@@ -0,0 +1,285 @@
+use std::{fmt::Display, str::FromStr};
+
+use keycode::KeyMappingId;
+use strum::IntoEnumIterator;
+
+use super::{ClickInput, InputAction, InputKey};
+
+const MAX_SUGGESTIONS: usize = 3;
+
+const KEY_ALIASES: &[(&str, &str)] = &[
+    ("Esc", "Escape"),
+    ("Return", "Enter"),
+    ("Del", "Delete"),
+    ("Ins", "Insert"),
+    ("PgUp", "PageUp"),
+    ("PgDn", "PageDown"),
+    ("Up", "ArrowUp"),
+    ("Down", "ArrowDown"),
+    ("Left", "ArrowLeft"),
+    ("Right", "ArrowRight"),
+    ("Plus", "Equal"),
+    ("=", "Equal"),
+    ("-", "Minus"),
+    ("[", "BracketLeft"),
+    ("]", "BracketRight"),
+    (",", "Comma"),
+    (".", "Period"),
+    ("/", "Slash"),
+    ("\\", "Backslash"),
+    (";", "Semicolon"),
+    ("'", "Quote"),
+    ("`", "Backquote"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ParseActionError {
+    Empty,
+    UnknownModifier(String),
+    UnknownMouseButton(String),
+    UnknownKey {
+        name: String,
+        suggestions: Vec<String>,
+    },
+}
+
+impl Display for ParseActionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "Empty key binding."),
+            Self::UnknownModifier(name) => write!(
+                f,
+                "Unknown modifier \"{name}\", expected one of Super, Ctrl, Alt, Shift."
+            ),
+            Self::UnknownMouseButton(name) => write!(
+                f,
+                "Unknown mouse button \"{name}\", expected one of Left, Right, Middle, Touch."
+            ),
+            Self::UnknownKey { name, suggestions } => {
+                write!(f, "Unknown key \"{name}\".")?;
+                if !suggestions.is_empty() {
+                    write!(f, " Did you mean {}?", suggestions.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for InputAction {
+    type Err = ParseActionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = segments.pop().filter(|key| !key.is_empty());
+        let key = key.ok_or(ParseActionError::Empty)?;
+
+        let mut action = InputAction {
+            shift: false,
+            ctrl: false,
+            alt: false,
+            super_key: false,
+            key: parse_key(key)?,
+        };
+
+        for modifier in segments {
+            match modifier.to_lowercase().as_str() {
+                "super" | "meta" | "win" => action.super_key = true,
+                "ctrl" | "control" => action.ctrl = true,
+                "alt" => action.alt = true,
+                "shift" => action.shift = true,
+                _ => return Err(ParseActionError::UnknownModifier(modifier.to_string())),
+            }
+        }
+
+        Ok(action)
+    }
+}
+
+fn parse_key(name: &str) -> Result<InputKey, ParseActionError> {
+    if let Some((prefix, button)) = name.split_once(':') {
+        if prefix.eq_ignore_ascii_case("mouse") {
+            let click = match button.to_lowercase().as_str() {
+                "left" => ClickInput::LeftClick,
+                "right" => ClickInput::RightClick,
+                "middle" => ClickInput::MiddleClick,
+                "touch" => ClickInput::Touch,
+                _ => return Err(ParseActionError::UnknownMouseButton(button.to_string())),
+            };
+            return Ok(InputKey::Mouse(click));
+        }
+    }
+
+    let canonical = KEY_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map(|(_, canonical)| *canonical)
+        .unwrap_or(name);
+
+    KeyMappingId::iter()
+        .find(|key| {
+            key.to_string().eq_ignore_ascii_case(canonical)
+                || key_name(key).eq_ignore_ascii_case(canonical)
+        })
+        .map(InputKey::Keyboard)
+        .ok_or_else(|| ParseActionError::UnknownKey {
+            name: name.to_string(),
+            suggestions: suggest_keys(name),
+        })
+}
+
+/// Short name of a key, e.g. `Z` for `UsZ` and `1` for `Digit1`
+pub(super) fn key_name(key: &KeyMappingId) -> String {
+    let name = key.to_string();
+    for prefix in ["Us", "Digit"] {
+        if let Some(rest) = name.strip_prefix(prefix) {
+            if rest.len() == 1 {
+                return rest.to_string();
+            }
+        }
+    }
+    name
+}
+
+pub(super) fn click_name(click: &ClickInput) -> &'static str {
+    match click {
+        ClickInput::LeftClick => "Left",
+        ClickInput::RightClick => "Right",
+        ClickInput::MiddleClick => "Middle",
+        ClickInput::Touch => "Touch",
+    }
+}
+
+fn suggest_keys(name: &str) -> Vec<String> {
+    let name = name.to_lowercase();
+    let max_distance = (name.len() / 3).max(1);
+    let mut candidates: Vec<(usize, String)> = KeyMappingId::iter()
+        .map(|key| key_name(&key))
+        .chain(KEY_ALIASES.iter().map(|(alias, _)| alias.to_string()))
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// Optimal string alignment distance, counting transpositions as a single edit
+fn edit_distance(left: &str, right: &str) -> usize {
+    let left: Vec<char> = left.chars().collect();
+    let right: Vec<char> = right.chars().collect();
+    let mut distances = vec![vec![0; right.len() + 1]; left.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in distances[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=left.len() {
+        for j in 1..=right.len() {
+            let cost = (left[i - 1] != right[j - 1]) as usize;
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && left[i - 1] == right[j - 2] && left[i - 2] == right[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[left.len()][right.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use keycode::KeyMappingId;
+
+    use super::{edit_distance, ParseActionError};
+    use crate::platform::{ClickInput, InputAction, InputKey};
+
+    fn parse(chord: &str) -> Result<InputAction, ParseActionError> {
+        chord.parse()
+    }
+
+    #[test]
+    fn chords() {
+        let action = parse("Ctrl+Shift+Z").unwrap();
+        assert!(action.ctrl && action.shift && !action.alt && !action.super_key);
+        assert_eq!(action.key, InputKey::Keyboard(KeyMappingId::UsZ));
+        assert_eq!(action.to_string(), "Ctrl+Shift+Z");
+
+        // modifiers in any order and case, aliases and spaces around segments
+        let action = parse("win + alt + esc").unwrap();
+        assert!(action.super_key && action.alt);
+        assert_eq!(action.key, InputKey::Keyboard(KeyMappingId::Escape));
+        assert_eq!(action.to_string(), "Super+Alt+Escape");
+
+        assert_eq!(
+            parse("1").unwrap().key,
+            InputKey::Keyboard(KeyMappingId::Digit1)
+        );
+        assert_eq!(
+            parse("Shift+Mouse:Right").unwrap().key,
+            InputKey::Mouse(ClickInput::RightClick)
+        );
+        assert_eq!(parse("Ctrl+Up").unwrap(), parse("Control+ArrowUp").unwrap());
+    }
+
+    #[test]
+    fn invalid_chords() {
+        assert_eq!(parse(""), Err(ParseActionError::Empty));
+        assert_eq!(parse("Ctrl+"), Err(ParseActionError::Empty));
+        assert_eq!(
+            parse("Hyper+Z"),
+            Err(ParseActionError::UnknownModifier("Hyper".to_string()))
+        );
+        assert_eq!(
+            parse("Mouse:Back"),
+            Err(ParseActionError::UnknownMouseButton("Back".to_string()))
+        );
+        assert_eq!(
+            parse("Ctrl+Hyper+Z").unwrap_err().to_string(),
+            "Unknown modifier \"Hyper\", expected one of Super, Ctrl, Alt, Shift."
+        );
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let Err(ParseActionError::UnknownKey { name, suggestions }) = parse("Ctrl+Escpe") else {
+            panic!("Escpe should be an unknown key");
+        };
+        assert_eq!(name, "Escpe");
+        assert_eq!(suggestions.first().map(String::as_str), Some("Escape"));
+
+        // transposed letters are a single edit
+        let Err(err @ ParseActionError::UnknownKey { .. }) = parse("Tba") else {
+            panic!("Tba should be an unknown key");
+        };
+        assert!(err
+            .to_string()
+            .starts_with("Unknown key \"Tba\". Did you mean Tab"));
+
+        assert_eq!(
+            parse("Xyzzyplugh"),
+            Err(ParseActionError::UnknownKey {
+                name: "Xyzzyplugh".to_string(),
+                suggestions: Vec::new(),
+            })
+        );
+        assert_eq!(
+            parse("Xyzzyplugh").unwrap_err().to_string(),
+            "Unknown key \"Xyzzyplugh\"."
+        );
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("tab", "tab"), 0);
+        assert_eq!(edit_distance("tba", "tab"), 1);
+        assert_eq!(edit_distance("escpe", "escape"), 1);
+        assert_eq!(edit_distance("", "f1"), 2);
+    }
+}
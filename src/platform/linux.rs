@@ -1,8 +1,13 @@
 use super::{ClickInput, InputKey, VirtualInputDevice};
-use crate::{device_info::DeviceInfo, interfaces::PenStatus, utils::LogExpect};
+use crate::{
+    device_info::DeviceInfo,
+    interfaces::{Button, ButtonState, PenStatus},
+    utils::LogExpect,
+};
 use evdev::{
     uinput::{VirtualDevice, VirtualDeviceBuilder},
-    AbsInfo, AbsoluteAxisType, AttributeSet, EventType, InputEvent, Key, UinputAbsSetup,
+    AbsInfo, AbsoluteAxisType, AttributeSet, EventType, InputEvent, Key, RelativeAxisType,
+    UinputAbsSetup,
 };
 use keycode::KeyMap;
 use log::warn;
@@ -15,7 +20,13 @@ const ABS_RES: i32 = 1;
 const PRESSURE_MAX: i32 = 8192 - 1;
 const TILT_MIN: i32 = -128 + 1;
 const TILT_MAX: i32 = 128 - 1;
+const WHEEL_HI_RES_PER_TICK: i32 = 120;
+/// `BTN_0` to `BTN_9`
+const TABLET_LOW_KEYS: u32 = 10;
+/// `BTN_TRIGGER_HAPPY1` to `BTN_TRIGGER_HAPPY40`
+const TABLET_HIGH_KEYS: u32 = 40;
 
+/// The kernel destroys the uinput device once this is dropped and its file closed
 pub(crate) struct VirtualInput {
     evdev: VirtualDevice,
     buffer: Vec<InputEvent>, // todo: buffer?
@@ -47,6 +58,9 @@ impl VirtualInputDevice for VirtualInput {
         let abs_tilt_info = AbsInfo::new(0, TILT_MIN, TILT_MAX, ABS_FUZZ, ABS_FLAT, ABS_RES);
         let abs_tilt_x = UinputAbsSetup::new(AbsoluteAxisType::ABS_TILT_X, abs_tilt_info);
         let abs_tilt_y = UinputAbsSetup::new(AbsoluteAxisType::ABS_TILT_Y, abs_tilt_info);
+        let mut relative_axes = AttributeSet::<RelativeAxisType>::new();
+        relative_axes.insert(RelativeAxisType::REL_WHEEL);
+        relative_axes.insert(RelativeAxisType::REL_WHEEL_HI_RES);
 
         let virtual_device = VirtualDeviceBuilder::new()
             .log_expect("Failed to create virtual input device.")
@@ -63,6 +77,8 @@ impl VirtualInputDevice for VirtualInput {
             .log_expect("Failed to submit virtual input device capabilities.")
             .with_absolute_axis(&abs_tilt_y)
             .log_expect("Failed to submit virtual input device capabilities.")
+            .with_relative_axes(&relative_axes)
+            .log_expect("Failed to submit virtual input device capabilities.")
             .build()
             .log_expect("Failed to virtual input device.");
 
@@ -101,6 +117,7 @@ impl VirtualInputDevice for VirtualInput {
             ));
         }
         if let Some(pressure) = update_info.pressure {
+            let pressure = (pressure * PRESSURE_MAX as f32) as i32;
             self.buffer.push(InputEvent::new(
                 EventType::ABSOLUTE,
                 AbsoluteAxisType::ABS_PRESSURE.0,
@@ -112,27 +129,29 @@ impl VirtualInputDevice for VirtualInput {
         });
     }
 
-    fn submit_action(&mut self, action: super::InputAction, pressed: bool) {
+    fn submit_action(&mut self, action: &super::InputAction, pressed: bool) {
         self.buffer.clear();
+
+        let mut modifiers = Vec::with_capacity(4);
         if action.super_key {
-            self.buffer.push(InputEvent::new(EventType::KEY, 125u16, 1));
+            modifiers.push(125u16);
             // KEY_LEFTMETA
         }
         if action.ctrl {
-            self.buffer.push(InputEvent::new(EventType::KEY, 29u16, 1));
+            modifiers.push(29u16);
             // KEY_LEFTCTRL
         }
         if action.alt {
-            self.buffer.push(InputEvent::new(EventType::KEY, 56u16, 1));
-            // KEY_LEFTCTRL
+            modifiers.push(56u16);
+            // KEY_LEFTALT
         }
         if action.shift {
-            self.buffer.push(InputEvent::new(EventType::KEY, 42u16, 1));
-            // KEY_LEFTCTRL
+            modifiers.push(42u16);
+            // KEY_LEFTSHIFT
         }
 
-        let key = match action.key {
-            InputKey::Keyboard(key) => KeyMap::from(key).evdev,
+        let key = match &action.key {
+            InputKey::Keyboard(key) => KeyMap::from(*key).evdev,
             InputKey::Mouse(key) => match key {
                 ClickInput::LeftClick => 0x110,
                 ClickInput::RightClick => 0x111,
@@ -141,11 +160,91 @@ impl VirtualInputDevice for VirtualInput {
             },
         };
 
+        // press modifiers before the key, release them after it
+        if pressed {
+            for modifier in &modifiers {
+                self.buffer
+                    .push(InputEvent::new(EventType::KEY, *modifier, 1));
+            }
+            self.buffer.push(InputEvent::new(EventType::KEY, key, 1));
+        } else {
+            self.buffer.push(InputEvent::new(EventType::KEY, key, 0));
+            for modifier in modifiers.iter().rev() {
+                self.buffer
+                    .push(InputEvent::new(EventType::KEY, *modifier, 0));
+            }
+        }
+
+        self.evdev.emit(&self.buffer).unwrap_or_else(|_| {
+            warn!("Failed to emit input event.");
+        });
+    }
+
+    fn submit_button(&mut self, button: Button, state: ButtonState) {
+        let key = match button {
+            Button::PenTip => Key::BTN_TOUCH,
+            Button::PenPrimary => Key::BTN_STYLUS,
+            Button::PenSecondary => Key::BTN_STYLUS2,
+            Button::Eraser => Key::BTN_TOOL_RUBBER,
+            Button::Wheel => Key::BTN_MIDDLE,
+            Button::Tablet(index) => match tablet_key(index) {
+                Some(key) => key,
+                None => {
+                    warn!("{} has no key on the virtual device, ignoring.", button);
+                    return;
+                }
+            },
+        };
+
+        self.buffer.clear();
         self.buffer
-            .push(InputEvent::new(EventType::KEY, key, pressed as i32));
+            .push(InputEvent::new(EventType::KEY, key.code(), state.code()));
+        self.evdev.emit(&self.buffer).unwrap_or_else(|_| {
+            warn!("Failed to emit input event.");
+        });
+    }
 
+    fn submit_scroll(&mut self, ticks: i32) {
+        self.buffer.clear();
+        self.buffer.push(InputEvent::new(
+            EventType::RELATIVE,
+            RelativeAxisType::REL_WHEEL.0,
+            ticks,
+        ));
+        self.buffer.push(InputEvent::new(
+            EventType::RELATIVE,
+            RelativeAxisType::REL_WHEEL_HI_RES.0,
+            ticks * WHEEL_HI_RES_PER_TICK,
+        ));
         self.evdev.emit(&self.buffer).unwrap_or_else(|_| {
             warn!("Failed to emit input event.");
         });
     }
 }
+
+/// Tablet buttons beyond `BTN_9` continue at `BTN_TRIGGER_HAPPY1`
+fn tablet_key(index: u32) -> Option<Key> {
+    let code = match index.checked_sub(TABLET_LOW_KEYS) {
+        None => Key::BTN_0.code() as u32 + index,
+        Some(high) if high < TABLET_HIGH_KEYS => Key::BTN_TRIGGER_HAPPY1.code() as u32 + high,
+        Some(_) => return None,
+    };
+    Some(Key::new(code as u16))
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev::Key;
+
+    use super::tablet_key;
+
+    #[test]
+    fn tablet_keys() {
+        assert_eq!(tablet_key(0), Some(Key::BTN_0));
+        assert_eq!(tablet_key(9), Some(Key::BTN_9));
+        assert_eq!(tablet_key(10), Some(Key::BTN_TRIGGER_HAPPY1));
+        assert_eq!(tablet_key(49), Some(Key::BTN_TRIGGER_HAPPY40));
+        assert_eq!(tablet_key(50), None);
+        assert_eq!(tablet_key(u32::MAX), None);
+    }
+}
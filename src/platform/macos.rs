@@ -9,7 +9,17 @@ impl VirtualInputDevice for VirtualDevice {
     fn submit_cursor(&mut self, update_info: crate::interfaces::PenStatus) {
         todo!()
     }
-    fn submit_action(&mut self, action: super::InputAction, pressed: bool) {
+    fn submit_action(&mut self, action: &super::InputAction, pressed: bool) {
+        todo!()
+    }
+    fn submit_button(
+        &mut self,
+        button: crate::interfaces::Button,
+        state: crate::interfaces::ButtonState,
+    ) {
+        todo!()
+    }
+    fn submit_scroll(&mut self, ticks: i32) {
         todo!()
     }
 }
@@ -1,10 +1,14 @@
+mod action;
 mod linux;
 mod macos;
 mod windows;
 
 use keycode::KeyMappingId;
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
+pub(crate) use action::ParseActionError;
+
 #[cfg(target_os = "linux")]
 pub(crate) use linux::VirtualInput;
 
@@ -22,10 +26,13 @@ use crate::{
 pub(crate) trait VirtualInputDevice {
     fn new(device_info: &DeviceInfo) -> Self;
     fn submit_cursor(&mut self, update_info: PenStatus);
-    fn submit_action(&mut self, action: InputAction, pressed: bool);
+    fn submit_action(&mut self, action: &InputAction, pressed: bool);
+    fn submit_button(&mut self, button: Button, state: ButtonState);
+    /// Positive ticks scroll up
+    fn submit_scroll(&mut self, ticks: i32);
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) enum ClickInput {
     LeftClick,
     RightClick,
@@ -44,7 +51,7 @@ impl Display for ClickInput {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) enum InputKey {
     Keyboard(KeyMappingId),
     Mouse(ClickInput),
@@ -53,34 +60,51 @@ pub(crate) enum InputKey {
 impl Display for InputKey {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Keyboard(inner) => write!(f, "K({inner})"),
-            Self::Mouse(inner) => write!(f, "M({inner})"),
+            Self::Keyboard(inner) => write!(f, "{}", action::key_name(inner)),
+            Self::Mouse(inner) => write!(f, "Mouse:{}", action::click_name(inner)),
         }
     }
 }
 
+/// Key chord such as `Ctrl+Shift+Z`, `Super+Tab` or `Mouse:Right`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
 pub(crate) struct InputAction {
-    shift: bool,
-    ctrl: bool,
-    alt: bool,
-    super_key: bool,
-    key: InputKey, // use Option for modifiers-only situation?
+    pub(crate) shift: bool,
+    pub(crate) ctrl: bool,
+    pub(crate) alt: bool,
+    pub(crate) super_key: bool,
+    pub(crate) key: InputKey, // use Option for modifiers-only situation?
 }
 
 impl Display for InputAction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.super_key {
-            write!(f, "Super ")?;
+            write!(f, "Super+")?;
         }
         if self.ctrl {
-            write!(f, "Ctrl ")?;
+            write!(f, "Ctrl+")?;
         }
         if self.alt {
-            write!(f, "Alt ")?;
+            write!(f, "Alt+")?;
         }
         if self.shift {
-            write!(f, "Shift ")?;
+            write!(f, "Shift+")?;
         }
         write!(f, "{}", self.key)
     }
 }
+
+impl TryFrom<String> for InputAction {
+    type Error = ParseActionError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<InputAction> for String {
+    fn from(value: InputAction) -> Self {
+        value.to_string()
+    }
+}
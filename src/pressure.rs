@@ -0,0 +1,184 @@
+use serde::{Deserialize, Serialize};
+
+const BEZIER_ITERATIONS: usize = 24;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub(crate) struct PressureConfig {
+    pub(crate) curve: PressureCurve,
+    /// Normalized raw pressure required before any pressure is reported
+    pub(crate) threshold: f32,
+    /// Lower bound of the reported pressure while the pen is pressed
+    pub(crate) min: f32,
+    /// Upper bound of the reported pressure
+    pub(crate) max: f32,
+    /// Normalized raw pressure at which the pen tip is considered pressed
+    pub(crate) tip_activation: f32,
+    /// Normalized raw pressure at which a pressed pen tip is released
+    pub(crate) tip_release: f32,
+}
+
+impl Default for PressureConfig {
+    fn default() -> PressureConfig {
+        PressureConfig {
+            curve: PressureCurve::Linear,
+            threshold: 0.0,
+            min: 0.0,
+            max: 1.0,
+            tip_activation: 0.02,
+            tip_release: 0.01,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub(crate) enum PressureCurve {
+    #[default]
+    Linear,
+    Gamma(f32),
+    /// Cubic bezier from (0, 0) to (1, 1) with two control points
+    Bezier((f32, f32), (f32, f32)),
+}
+
+impl PressureCurve {
+    fn evaluate(&self, input: f32) -> f32 {
+        match self {
+            Self::Linear => input,
+            Self::Gamma(gamma) => input.powf(*gamma),
+            // bisection never reaches the end points, which steep curves would miss by a lot
+            Self::Bezier(..) if input <= 0.0 => 0.0,
+            Self::Bezier(..) if input >= 1.0 => 1.0,
+            Self::Bezier(first, second) => {
+                // x(t) is monotonic for control points within the unit square, so bisect on t
+                let (mut low, mut high) = (0.0, 1.0);
+                for _ in 0..BEZIER_ITERATIONS {
+                    let t = (low + high) / 2.0;
+                    if cubic_bezier(first.0, second.0, t) < input {
+                        low = t;
+                    } else {
+                        high = t;
+                    }
+                }
+                cubic_bezier(first.1, second.1, (low + high) / 2.0)
+            }
+        }
+    }
+}
+
+fn cubic_bezier(first: f32, second: f32, t: f32) -> f32 {
+    let rest = 1.0 - t;
+    3.0 * rest * rest * t * first + 3.0 * rest * t * t * second + t * t * t
+}
+
+impl PressureConfig {
+    /// Maps normalized raw pressure to normalized output pressure
+    pub(crate) fn apply(&self, input: f32) -> f32 {
+        let input = input.clamp(0.0, 1.0);
+        if input <= self.threshold {
+            return 0.0;
+        }
+        let input = (input - self.threshold) / (1.0 - self.threshold);
+        self.curve
+            .evaluate(input)
+            .clamp(0.0, 1.0)
+            .max(self.min)
+            .min(self.max)
+    }
+}
+
+/// Derives the pen tip state from raw pressure with hysteresis
+#[derive(Debug, Default)]
+pub(crate) struct TipDetector {
+    pressed: bool,
+}
+
+impl TipDetector {
+    pub(crate) fn update(&mut self, pressure_config: &PressureConfig, input: f32) -> bool {
+        self.pressed = if self.pressed {
+            input > pressure_config.tip_release
+        } else {
+            input >= pressure_config.tip_activation
+        };
+        self.pressed
+    }
+}
+
+pub(crate) fn print_curve_table(pressure_config: &PressureConfig, steps: usize) {
+    let steps = steps.max(2);
+    println!("{:>8} | {:>8}", "input", "output");
+    println!("{:->8}-+-{:->8}", "", "");
+    for step in 0..steps {
+        let input = step as f32 / (steps - 1) as f32;
+        println!("{:>8.3} | {:>8.3}", input, pressure_config.apply(input));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PressureConfig, PressureCurve, TipDetector};
+
+    fn assert_close(left: f32, right: f32) {
+        assert!((left - right).abs() < 1e-4, "{} is not {}", left, right);
+    }
+
+    fn with_curve(curve: PressureCurve) -> PressureConfig {
+        PressureConfig {
+            curve,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn curve_endpoints() {
+        for curve in [
+            PressureCurve::Linear,
+            PressureCurve::Gamma(0.5),
+            PressureCurve::Gamma(2.0),
+            PressureCurve::Bezier((0.2, 0.6), (0.4, 0.9)),
+            PressureCurve::Bezier((0.8, 0.0), (1.0, 0.2)),
+        ] {
+            let pressure_config = with_curve(curve);
+            assert_close(pressure_config.apply(0.0), 0.0);
+            assert_close(pressure_config.apply(1.0), 1.0);
+            // out of range input is clamped
+            assert_close(pressure_config.apply(-0.5), 0.0);
+            assert_close(pressure_config.apply(1.5), 1.0);
+        }
+    }
+
+    #[test]
+    fn curves() {
+        assert_close(with_curve(PressureCurve::Linear).apply(0.3), 0.3);
+        assert_close(with_curve(PressureCurve::Gamma(2.0)).apply(0.5), 0.25);
+        // control points on the diagonal make a straight line
+        let straight = with_curve(PressureCurve::Bezier((0.25, 0.25), (0.75, 0.75)));
+        assert_close(straight.apply(0.3), 0.3);
+        let soft = with_curve(PressureCurve::Bezier((0.0, 0.5), (0.5, 1.0)));
+        assert!(soft.apply(0.3) > 0.3);
+    }
+
+    #[test]
+    fn threshold_and_clipping() {
+        let pressure_config = PressureConfig {
+            threshold: 0.2,
+            min: 0.1,
+            max: 0.8,
+            ..Default::default()
+        };
+        assert_close(pressure_config.apply(0.2), 0.0);
+        assert_close(pressure_config.apply(0.22), 0.1);
+        assert_close(pressure_config.apply(0.6), 0.5);
+        assert_close(pressure_config.apply(1.0), 0.8);
+    }
+
+    #[test]
+    fn tip_hysteresis() {
+        let pressure_config = PressureConfig::default();
+        let mut tip_detector = TipDetector::default();
+        let states: Vec<bool> = [0.015, 0.02, 0.015, 0.011, 0.01, 0.015]
+            .into_iter()
+            .map(|input| tip_detector.update(&pressure_config, input))
+            .collect();
+        assert_eq!(states, [false, true, true, true, false, false]);
+    }
+}
@@ -0,0 +1,565 @@
+use std::collections::BTreeMap;
+
+use log::{debug, info, warn};
+use rusb::{DeviceHandle, Direction, GlobalContext, Recipient, RequestType};
+
+use super::{DESCRIPTOR_TIMEOUT, REQUEST_GET_DESCRIPTOR};
+use crate::{
+    device_info::DeviceInfo,
+    interfaces::Button,
+    report::{ButtonField, Field, ReportLayout},
+};
+
+const DESCRIPTOR_TYPE_REPORT: u16 = 0x22;
+const MAX_DESCRIPTOR_LENGTH: usize = 4096;
+/// Fields are decoded into 32 bit values
+const MAX_REPORT_SIZE: u32 = 32;
+/// Far beyond any real input report, bounds the work a broken descriptor can cause
+const MAX_REPORT_BITS: u32 = 4096 * 8;
+
+const PAGE_GENERIC_DESKTOP: u16 = 0x01;
+const PAGE_DIGITIZER: u16 = 0x0d;
+
+const USAGE_X: u16 = 0x30;
+const USAGE_Y: u16 = 0x31;
+
+const USAGE_DIGITIZER: u16 = 0x01;
+const USAGE_PEN: u16 = 0x02;
+const USAGE_TIP_PRESSURE: u16 = 0x30;
+const USAGE_IN_RANGE: u16 = 0x32;
+const USAGE_INVERT: u16 = 0x3c;
+const USAGE_X_TILT: u16 = 0x3d;
+const USAGE_Y_TILT: u16 = 0x3e;
+const USAGE_TIP_SWITCH: u16 = 0x42;
+const USAGE_BARREL_SWITCH: u16 = 0x44;
+const USAGE_ERASER: u16 = 0x45;
+const USAGE_SECONDARY_BARREL_SWITCH: u16 = 0x5a;
+
+const ITEM_TYPE_MAIN: u8 = 0;
+const ITEM_TYPE_GLOBAL: u8 = 1;
+const ITEM_TYPE_LOCAL: u8 = 2;
+const LONG_ITEM_PREFIX: u8 = 0xfe;
+
+const MAIN_INPUT: u8 = 0x8;
+const MAIN_COLLECTION: u8 = 0xa;
+const MAIN_END_COLLECTION: u8 = 0xc;
+
+const GLOBAL_USAGE_PAGE: u8 = 0x0;
+const GLOBAL_LOGICAL_MINIMUM: u8 = 0x1;
+const GLOBAL_LOGICAL_MAXIMUM: u8 = 0x2;
+const GLOBAL_PHYSICAL_MINIMUM: u8 = 0x3;
+const GLOBAL_PHYSICAL_MAXIMUM: u8 = 0x4;
+const GLOBAL_UNIT_EXPONENT: u8 = 0x5;
+const GLOBAL_UNIT: u8 = 0x6;
+const GLOBAL_REPORT_SIZE: u8 = 0x7;
+const GLOBAL_REPORT_ID: u8 = 0x8;
+const GLOBAL_REPORT_COUNT: u8 = 0x9;
+const GLOBAL_PUSH: u8 = 0xa;
+const GLOBAL_POP: u8 = 0xb;
+
+const LOCAL_USAGE: u8 = 0x0;
+const LOCAL_USAGE_MINIMUM: u8 = 0x1;
+
+const INPUT_CONSTANT: u32 = 1 << 0;
+const INPUT_VARIABLE: u32 = 1 << 1;
+
+const UNIT_CENTIMETER: u32 = 0x11;
+const UNIT_INCH: u32 = 0x13;
+
+/// Pen layout and dimensions described by a HID report descriptor
+#[derive(Debug, Default)]
+pub(crate) struct Digitizer {
+    pub(crate) reports: Vec<ReportLayout>,
+    pub(crate) x_max: u32,
+    pub(crate) y_max: u32,
+    pub(crate) pressure_max: u32,
+    /// Lines per inch
+    pub(crate) resolution: Option<f32>,
+    /// Longest input report including the report ID
+    pub(crate) packet_length: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct GlobalItems {
+    usage_page: u16,
+    logical_minimum: i32,
+    logical_maximum: i32,
+    physical_minimum: i32,
+    physical_maximum: i32,
+    unit_exponent: i32,
+    unit: u32,
+    report_size: u32,
+    report_id: Option<u8>,
+    report_count: u32,
+}
+
+#[derive(Debug, Default)]
+struct LocalItems {
+    /// Usage page in the high half, usage in the low half
+    usages: Vec<u32>,
+    usage_minimum: Option<u32>,
+}
+
+impl LocalItems {
+    fn usage(&self, index: usize) -> Option<u32> {
+        self.usages.get(index).or(self.usages.last()).copied()
+    }
+}
+
+impl GlobalItems {
+    fn full_usage(&self, data: u32, size: usize) -> u32 {
+        if size == 4 {
+            data
+        } else {
+            ((self.usage_page as u32) << 16) | data
+        }
+    }
+
+    /// Some descriptors declare unsigned maximums with too few bytes
+    fn maximum(&self) -> i64 {
+        if self.logical_maximum < self.logical_minimum {
+            self.logical_maximum as u32 as i64
+        } else {
+            self.logical_maximum as i64
+        }
+    }
+
+    fn field(&self, bit_offset: u32) -> Field {
+        Field {
+            offset: (bit_offset / 8) as usize,
+            bit: (bit_offset % 8) as u8,
+            bits: self.report_size as u8,
+            endian: Default::default(),
+            signed: self.logical_minimum < 0,
+            high: None,
+        }
+    }
+
+    fn physical_inches(&self) -> Option<f32> {
+        let length =
+            (self.physical_maximum - self.physical_minimum) as f32 * 10f32.powi(self.unit_exponent);
+        let inches = match self.unit {
+            UNIT_CENTIMETER => length / 2.54,
+            UNIT_INCH => length,
+            _ => return None,
+        };
+        (inches > 0.0).then_some(inches)
+    }
+}
+
+#[derive(Debug, Default)]
+struct PenReportBuilder {
+    layout: ReportLayout,
+    eraser: Option<ButtonField>,
+    bits: u32,
+}
+
+impl PenReportBuilder {
+    fn assign(
+        &mut self,
+        digitizer: &mut Digitizer,
+        global: &GlobalItems,
+        usage: u32,
+        bit_offset: u32,
+    ) {
+        let field = global.field(bit_offset);
+        let button = |button| ButtonField {
+            byte: (bit_offset / 8) as usize,
+            bit: (bit_offset % 8) as u8,
+            button,
+        };
+        match ((usage >> 16) as u16, usage as u16) {
+            (PAGE_GENERIC_DESKTOP, USAGE_X) => {
+                digitizer.x_max = global.maximum() as u32;
+                if digitizer.resolution.is_none() {
+                    digitizer.resolution = global
+                        .physical_inches()
+                        .map(|inches| global.maximum() as f32 / inches);
+                }
+                self.layout.x = Some(field);
+            }
+            (PAGE_GENERIC_DESKTOP, USAGE_Y) => {
+                digitizer.y_max = global.maximum() as u32;
+                self.layout.y = Some(field);
+            }
+            (PAGE_DIGITIZER, USAGE_TIP_PRESSURE) => {
+                digitizer.pressure_max = global.maximum() as u32;
+                self.layout.pressure = Some(field);
+            }
+            (PAGE_DIGITIZER, USAGE_X_TILT) => self.layout.tilt_x = Some(field),
+            (PAGE_DIGITIZER, USAGE_Y_TILT) => self.layout.tilt_y = Some(field),
+            (PAGE_DIGITIZER, USAGE_IN_RANGE) => self.layout.in_range = Some(field),
+            (PAGE_DIGITIZER, USAGE_TIP_SWITCH) => self.layout.buttons.push(button(Button::PenTip)),
+            (PAGE_DIGITIZER, USAGE_BARREL_SWITCH) => {
+                self.layout.buttons.push(button(Button::PenPrimary))
+            }
+            (PAGE_DIGITIZER, USAGE_SECONDARY_BARREL_SWITCH) => {
+                self.layout.buttons.push(button(Button::PenSecondary))
+            }
+            // the eraser end is in range, which is what the eraser tool means here
+            (PAGE_DIGITIZER, USAGE_INVERT) => self.layout.buttons.push(button(Button::Eraser)),
+            // pressing with the eraser, only used when the pen can't report inversion
+            (PAGE_DIGITIZER, USAGE_ERASER) => self.eraser = Some(button(Button::Eraser)),
+            _ => {}
+        }
+    }
+}
+
+/// Collects the pen usages of all reports inside digitizer or pen application collections
+pub(crate) fn parse_report_descriptor(descriptor: &[u8]) -> Option<Digitizer> {
+    let mut global = GlobalItems::default();
+    let mut global_stack = Vec::new();
+    let mut local = LocalItems::default();
+    // whether each open collection is a pen application collection, or inside one
+    let mut collections: Vec<bool> = Vec::new();
+    let mut reports: BTreeMap<Option<u8>, PenReportBuilder> = BTreeMap::new();
+    let mut digitizer = Digitizer::default();
+
+    let mut position = 0;
+    while position < descriptor.len() {
+        let prefix = descriptor[position];
+        if prefix == LONG_ITEM_PREFIX {
+            let data_size = *descriptor.get(position + 1)? as usize;
+            position += 3 + data_size;
+            continue;
+        }
+        let size = match prefix & 0b11 {
+            3 => 4,
+            size => size as usize,
+        };
+        let item_type = (prefix >> 2) & 0b11;
+        let tag = prefix >> 4;
+        let bytes = descriptor.get(position + 1..position + 1 + size)?;
+        position += 1 + size;
+
+        let data = bytes
+            .iter()
+            .rev()
+            .fold(0u32, |value, byte| (value << 8) | *byte as u32);
+        let signed_data = match size {
+            1 => data as u8 as i8 as i32,
+            2 => data as u16 as i16 as i32,
+            _ => data as i32,
+        };
+
+        match item_type {
+            ITEM_TYPE_MAIN => {
+                match tag {
+                    MAIN_COLLECTION => {
+                        let in_pen = collections.last().copied().unwrap_or(false);
+                        let is_pen = local.usage(0).is_some_and(|usage| {
+                            usage == (PAGE_DIGITIZER as u32) << 16 | USAGE_DIGITIZER as u32
+                                || usage == (PAGE_DIGITIZER as u32) << 16 | USAGE_PEN as u32
+                        });
+                        collections.push(in_pen || is_pen);
+                    }
+                    MAIN_END_COLLECTION => {
+                        collections.pop();
+                    }
+                    MAIN_INPUT => {
+                        let report = reports.entry(global.report_id).or_default();
+                        let in_pen = collections.last().copied().unwrap_or(false);
+                        let end = global
+                            .report_count
+                            .checked_mul(global.report_size)
+                            .and_then(|bits| bits.checked_add(report.bits))
+                            .filter(|end| *end <= MAX_REPORT_BITS);
+                        let Some(end) = end else {
+                            debug!(
+                                "Report descriptor declares a report longer than {} bits.",
+                                MAX_REPORT_BITS
+                            );
+                            return None;
+                        };
+                        let start = report.bits;
+                        report.bits = end;
+                        for index in 0..global.report_count {
+                            let bit_offset = start + index * global.report_size;
+                            if !in_pen || data & INPUT_CONSTANT != 0 || data & INPUT_VARIABLE == 0 {
+                                continue;
+                            }
+                            let usage = match local.usage_minimum {
+                                Some(minimum) if local.usages.is_empty() => {
+                                    minimum.checked_add(index)
+                                }
+                                _ => local.usage(index as usize),
+                            };
+                            let Some(usage) = usage else {
+                                continue;
+                            };
+                            // leave room for the report ID
+                            let bit_offset = bit_offset + global.report_id.map_or(0, |_| 8);
+                            report.assign(&mut digitizer, &global, usage, bit_offset);
+                        }
+                    }
+                    _ => {}
+                }
+                local = LocalItems::default();
+            }
+            ITEM_TYPE_GLOBAL => match tag {
+                GLOBAL_USAGE_PAGE => global.usage_page = data as u16,
+                GLOBAL_LOGICAL_MINIMUM => global.logical_minimum = signed_data,
+                GLOBAL_LOGICAL_MAXIMUM => global.logical_maximum = signed_data,
+                GLOBAL_PHYSICAL_MINIMUM => global.physical_minimum = signed_data,
+                GLOBAL_PHYSICAL_MAXIMUM => global.physical_maximum = signed_data,
+                // a 4 bit two's complement nibble
+                GLOBAL_UNIT_EXPONENT => {
+                    global.unit_exponent = if data & 0x8 != 0 {
+                        (data & 0xf) as i32 - 16
+                    } else {
+                        (data & 0xf) as i32
+                    }
+                }
+                GLOBAL_UNIT => global.unit = data,
+                GLOBAL_REPORT_SIZE if data > MAX_REPORT_SIZE => {
+                    debug!(
+                        "Report descriptor declares a report size of {} bits, more than {}.",
+                        data, MAX_REPORT_SIZE
+                    );
+                    return None;
+                }
+                GLOBAL_REPORT_SIZE => global.report_size = data,
+                GLOBAL_REPORT_ID => global.report_id = Some(data as u8),
+                GLOBAL_REPORT_COUNT => global.report_count = data,
+                GLOBAL_PUSH => global_stack.push(global),
+                GLOBAL_POP => global = global_stack.pop().unwrap_or_default(),
+                _ => {}
+            },
+            ITEM_TYPE_LOCAL => match tag {
+                LOCAL_USAGE => local.usages.push(global.full_usage(data, size)),
+                LOCAL_USAGE_MINIMUM => local.usage_minimum = Some(global.full_usage(data, size)),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    for (report_id, mut report) in reports {
+        let packet_length = (report.bits as usize).div_ceil(8) + report_id.map_or(0, |_| 1);
+        digitizer.packet_length = digitizer.packet_length.max(packet_length);
+        if report.layout.x.is_none() || report.layout.y.is_none() {
+            continue;
+        }
+        let has_invert = report
+            .layout
+            .buttons
+            .iter()
+            .any(|field| field.button == Button::Eraser);
+        if let (Some(eraser), false) = (report.eraser.take(), has_invert) {
+            report.layout.buttons.push(eraser);
+        }
+        report.layout.report_id = report_id;
+        digitizer.reports.push(report.layout);
+    }
+
+    if digitizer.reports.is_empty() || digitizer.x_max == 0 || digitizer.y_max == 0 {
+        None
+    } else {
+        Some(digitizer)
+    }
+}
+
+impl Digitizer {
+    fn apply(self, device_info: &mut DeviceInfo) {
+        device_info.width = self.x_max as f32;
+        device_info.height = self.y_max as f32;
+        if self.pressure_max > 0 {
+            device_info.pressure_max = self.pressure_max as f32;
+        }
+        if device_info.resolution.is_none() {
+            device_info.resolution = self.resolution;
+        }
+        device_info.packet_length = device_info.packet_length.max(self.packet_length);
+        for button in self
+            .reports
+            .iter()
+            .flat_map(|layout| layout.buttons.iter().map(|field| field.button))
+        {
+            if !device_info.button_available.contains(&button) {
+                device_info.button_available.push(button);
+            }
+        }
+        device_info.reports = self.reports;
+    }
+}
+
+/// Builds report layouts from the first of `interfaces` that describes a pen
+pub(crate) fn discover(
+    handle: &DeviceHandle<GlobalContext>,
+    device_info: &mut DeviceInfo,
+    interfaces: &[u8],
+) {
+    for interface in interfaces {
+        let descriptor = match read_report_descriptor(handle, *interface) {
+            Ok(descriptor) => descriptor,
+            Err(err) => {
+                debug!(
+                    "Failed to read report descriptor of interface {} of {}: {}.",
+                    interface, device_info.id, err
+                );
+                continue;
+            }
+        };
+        debug!(
+            "Report descriptor of interface {} of {}: {:02x?}",
+            interface, device_info.id, descriptor
+        );
+        if let Some(digitizer) = parse_report_descriptor(&descriptor) {
+            info!(
+                "Discovered pen reports on interface {} of {}.",
+                interface, device_info.id
+            );
+            debug!("Discovered digitizer: {:?}", digitizer);
+            digitizer.apply(device_info);
+            device_info.interface = Some(*interface);
+            return;
+        }
+    }
+    warn!(
+        "Device {} has no report layouts and no digitizer report descriptor.",
+        device_info.id
+    );
+}
+
+fn read_report_descriptor(
+    handle: &DeviceHandle<GlobalContext>,
+    interface: u8,
+) -> rusb::Result<Vec<u8>> {
+    let mut buffer = vec![0; MAX_DESCRIPTOR_LENGTH];
+    let length = handle.read_control(
+        rusb::request_type(Direction::In, RequestType::Standard, Recipient::Interface),
+        REQUEST_GET_DESCRIPTOR,
+        DESCRIPTOR_TYPE_REPORT << 8,
+        interface as u16,
+        &mut buffer,
+        DESCRIPTOR_TIMEOUT,
+    )?;
+    buffer.truncate(length);
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_report_descriptor;
+    use crate::{interfaces::Button, report::Field};
+
+    /// Pen descriptor Linux's hid-uclogic builds for v2 Huion tablets,
+    /// filled in for a 10 x 6.25 inch tablet at 5080 lpi with 8192 pressure levels
+    const HUION_V2_PEN: &[u8] = &[
+        0x05, 0x0d, // usage page (digitizer)
+        0x09, 0x02, // usage (pen)
+        0xa1, 0x01, // collection (application)
+        0x85, 0x08, //   report id (8)
+        0x09, 0x20, //   usage (stylus)
+        0xa0, //         collection (physical)
+        0x14, //           logical minimum (0)
+        0x25, 0x01, //     logical maximum (1)
+        0x75, 0x01, //     report size (1)
+        0x09, 0x42, //     usage (tip switch)
+        0x09, 0x44, //     usage (barrel switch)
+        0x09, 0x46, //     usage (tablet pick)
+        0x95, 0x03, //     report count (3)
+        0x81, 0x02, //     input (variable)
+        0x95, 0x03, //     report count (3)
+        0x81, 0x03, //     input (constant, variable)
+        0x09, 0x32, //     usage (in range)
+        0x95, 0x01, //     report count (1)
+        0x81, 0x02, //     input (variable)
+        0x95, 0x01, //     report count (1)
+        0x81, 0x03, //     input (constant, variable)
+        0x95, 0x01, //     report count (1)
+        0xa4, //           push
+        0x05, 0x01, //     usage page (desktop)
+        0x65, 0x13, //     unit (inch)
+        0x55, 0xfd, //     unit exponent (-3)
+        0x75, 0x20, //     report size (32)
+        0x34, //           physical minimum (0)
+        0x09, 0x30, //     usage (x)
+        0x27, 0x70, 0xc6, 0x00, 0x00, // logical maximum (50800)
+        0x47, 0x10, 0x27, 0x00, 0x00, // physical maximum (10000)
+        0x81, 0x02, //     input (variable)
+        0x09, 0x31, //     usage (y)
+        0x27, 0x06, 0x7c, 0x00, 0x00, // logical maximum (31750)
+        0x47, 0x6a, 0x18, 0x00, 0x00, // physical maximum (6250)
+        0x81, 0x02, //     input (variable)
+        0xb4, //           pop
+        0x09, 0x30, //     usage (tip pressure)
+        0x75, 0x10, //     report size (16)
+        0x27, 0xff, 0x1f, 0x00, 0x00, // logical maximum (8191)
+        0x81, 0x02, //     input (variable)
+        0x54, //           unit exponent (0)
+        0x65, 0x14, //     unit (degrees)
+        0x35, 0xc4, //     physical minimum (-60)
+        0x45, 0x3c, //     physical maximum (60)
+        0x15, 0xc4, //     logical minimum (-60)
+        0x25, 0x3c, //     logical maximum (60)
+        0x75, 0x08, //     report size (8)
+        0x95, 0x02, //     report count (2)
+        0x09, 0x3d, //     usage (x tilt)
+        0x09, 0x3e, //     usage (y tilt)
+        0x81, 0x02, //     input (variable)
+        0xc0, //         end collection
+        0xc0, //       end collection
+    ];
+
+    /// Offset, bit, bits and signedness of a field
+    fn position(field: &Option<Field>) -> (usize, u8, u8, bool) {
+        let field = field.as_ref().expect("missing field");
+        (field.offset, field.bit, field.bits, field.signed)
+    }
+
+    #[test]
+    fn huion_v2_pen_descriptor() {
+        let digitizer = parse_report_descriptor(HUION_V2_PEN).expect("no pen report");
+        assert_eq!(digitizer.x_max, 50800);
+        assert_eq!(digitizer.y_max, 31750);
+        assert_eq!(digitizer.pressure_max, 8191);
+        let resolution = digitizer.resolution.expect("no resolution");
+        assert!(
+            (resolution - 5080.0).abs() < 0.5,
+            "resolution {}",
+            resolution
+        );
+        assert_eq!(digitizer.packet_length, 14);
+
+        assert_eq!(digitizer.reports.len(), 1);
+        let layout = &digitizer.reports[0];
+        assert_eq!(layout.report_id, Some(8));
+        // the report ID takes the first byte
+        assert_eq!(position(&layout.x), (2, 0, 32, false));
+        assert_eq!(position(&layout.y), (6, 0, 32, false));
+        assert_eq!(position(&layout.pressure), (10, 0, 16, false));
+        assert_eq!(position(&layout.tilt_x), (12, 0, 8, true));
+        assert_eq!(position(&layout.tilt_y), (13, 0, 8, true));
+        assert_eq!(position(&layout.in_range), (1, 6, 1, false));
+        let buttons: Vec<_> = layout
+            .buttons
+            .iter()
+            .map(|field| (field.byte, field.bit, field.button))
+            .collect();
+        assert_eq!(
+            buttons,
+            vec![(1, 0, Button::PenTip), (1, 1, Button::PenPrimary)]
+        );
+    }
+
+    /// `HUION_V2_PEN` with the item at `position` replaced by `item`
+    fn patched(position: usize, length: usize, item: &[u8]) -> Vec<u8> {
+        let mut descriptor = HUION_V2_PEN.to_vec();
+        descriptor.splice(position..position + length, item.iter().copied());
+        descriptor
+    }
+
+    #[test]
+    fn oversized_reports_are_rejected() {
+        let position = HUION_V2_PEN
+            .windows(2)
+            .position(|item| item == [0x75, 0x20])
+            .unwrap();
+        // report size (64) for the axes
+        assert!(parse_report_descriptor(&patched(position, 2, &[0x75, 0x40])).is_none());
+        // report count (0xffffffff) for the axes, whose report size is 32
+        let descriptor = patched(position, 2, &[0x75, 0x20, 0x97, 0xff, 0xff, 0xff, 0xff]);
+        assert!(parse_report_descriptor(&descriptor).is_none());
+    }
+}
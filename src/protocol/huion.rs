@@ -0,0 +1,155 @@
+use log::{debug, info, warn};
+use rusb::{DeviceHandle, Direction, GlobalContext, Recipient, RequestType};
+
+use super::{DESCRIPTOR_TIMEOUT, REQUEST_GET_DESCRIPTOR};
+use crate::device_info::DeviceInfo;
+
+const DESCRIPTOR_TYPE_STRING: u16 = 0x03;
+const LANGUAGE_ID_EN_US: u16 = 0x0409;
+
+/// Reading this descriptor switches v2 firmware into full resolution mode
+const PARAMETERS_V2_INDEX: u8 = 200;
+/// Older firmware only knows this one
+const PARAMETERS_V1_INDEX: u8 = 100;
+
+const PARAMETERS_V2_LENGTH: usize = 18;
+const PARAMETERS_V1_LENGTH: usize = 12;
+
+/// Tablet parameters packed into the parameter string descriptors
+#[derive(Debug, PartialEq)]
+struct Parameters {
+    x_max: u32,
+    y_max: u32,
+    pressure_max: u32,
+    /// Lines per inch
+    resolution: u32,
+}
+
+impl Parameters {
+    fn decode(index: u8, descriptor: &[u8]) -> Option<Parameters> {
+        let le16 = |offset: usize| u16::from_le_bytes([descriptor[offset], descriptor[offset + 1]]);
+        let le24 = |offset: usize| {
+            u32::from_le_bytes([
+                descriptor[offset],
+                descriptor[offset + 1],
+                descriptor[offset + 2],
+                0,
+            ])
+        };
+        let parameters = match index {
+            PARAMETERS_V2_INDEX if descriptor.len() >= PARAMETERS_V2_LENGTH => Parameters {
+                x_max: le24(2),
+                y_max: le24(5),
+                pressure_max: le16(8) as u32,
+                resolution: le16(10) as u32,
+            },
+            PARAMETERS_V1_INDEX if descriptor.len() >= PARAMETERS_V1_LENGTH => Parameters {
+                x_max: le16(2) as u32,
+                y_max: le16(4) as u32,
+                pressure_max: le16(8) as u32,
+                resolution: le16(10) as u32,
+            },
+            _ => return None,
+        };
+        // unsupported firmware answers with zeroes
+        if (parameters.x_max == 0) || (parameters.y_max == 0) || (parameters.resolution == 0) {
+            None
+        } else {
+            Some(parameters)
+        }
+    }
+
+    fn apply(&self, device_info: &mut DeviceInfo) {
+        let discovered = [
+            ("width", &mut device_info.width, self.x_max as f32),
+            ("height", &mut device_info.height, self.y_max as f32),
+            (
+                "pressure_max",
+                &mut device_info.pressure_max,
+                self.pressure_max as f32,
+            ),
+        ];
+        for (name, field, value) in discovered {
+            if !device_info.provided.contains(name) {
+                *field = value;
+            } else if *field != value {
+                warn!(
+                    "Device database entry for {} has {} {}, but the device reports {}, using the latter.",
+                    device_info.id, name, field, value
+                );
+                *field = value;
+            }
+        }
+        match device_info.resolution {
+            Some(resolution) if resolution != self.resolution as f32 => {
+                warn!(
+                    "Device database entry for {} has resolution {}, but the device reports {}, using the latter.",
+                    device_info.id, resolution, self.resolution
+                );
+                device_info.resolution = Some(self.resolution as f32);
+            }
+            Some(_) => {}
+            None => device_info.resolution = Some(self.resolution as f32),
+        }
+    }
+}
+
+/// Switches the tablet into full resolution mode and fills in the parameters it reports
+pub(super) fn initialize(handle: &DeviceHandle<GlobalContext>, device_info: &mut DeviceInfo) {
+    for index in [PARAMETERS_V2_INDEX, PARAMETERS_V1_INDEX] {
+        match read_raw_string_descriptor(handle, index) {
+            Ok(descriptor) => {
+                debug!(
+                    "Huion parameter descriptor {} of {}: {:02x?}",
+                    index, device_info.id, descriptor
+                );
+                match Parameters::decode(index, &descriptor) {
+                    Some(parameters) => {
+                        if index == PARAMETERS_V2_INDEX {
+                            info!("Switched {} into full resolution mode.", device_info.id);
+                        }
+                        debug!(
+                            "Discovered parameters of {}: {:?}",
+                            device_info.id, parameters
+                        );
+                        parameters.apply(device_info);
+                    }
+                    None => {
+                        warn!(
+                            "Failed to decode parameters of {}, using device database values.",
+                            device_info.id
+                        );
+                    }
+                }
+                return;
+            }
+            Err(err) => {
+                debug!(
+                    "Failed to read Huion parameter descriptor {} of {}: {}.",
+                    index, device_info.id, err
+                );
+            }
+        }
+    }
+    warn!(
+        "Failed to switch {} into full resolution mode, reports may be truncated.",
+        device_info.id
+    );
+}
+
+/// Reads a string descriptor without decoding it, as Huion packs binary data into them
+pub(super) fn read_raw_string_descriptor(
+    handle: &DeviceHandle<GlobalContext>,
+    index: u8,
+) -> rusb::Result<Vec<u8>> {
+    let mut buffer = [0; 255];
+    let length = handle.read_control(
+        rusb::request_type(Direction::In, RequestType::Standard, Recipient::Device),
+        REQUEST_GET_DESCRIPTOR,
+        (DESCRIPTOR_TYPE_STRING << 8) | index as u16,
+        LANGUAGE_ID_EN_US,
+        &mut buffer,
+        DESCRIPTOR_TIMEOUT,
+    )?;
+    Ok(buffer[..length].to_vec())
+}
@@ -0,0 +1,66 @@
+pub(crate) mod hid;
+mod huion;
+mod xppen;
+
+use std::time::Duration;
+
+use log::{info, warn};
+use rusb::{DeviceHandle, Direction, GlobalContext, Recipient, RequestType};
+use serde::Deserialize;
+
+use crate::device_info::DeviceInfo;
+
+const OUTPUT_REPORT_TIMEOUT: Duration = Duration::from_millis(500);
+pub(crate) const DESCRIPTOR_TIMEOUT: Duration = Duration::from_millis(500);
+pub(crate) const REQUEST_GET_DESCRIPTOR: u8 = 0x06;
+const REQUEST_SET_REPORT: u8 = 0x09;
+const REPORT_TYPE_OUTPUT: u16 = 0x02;
+
+/// Vendor specific behaviour needed before a device sends usable reports
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub(crate) enum Protocol {
+    #[default]
+    Generic,
+    Huion,
+    XpPen,
+}
+
+/// May update `device_info` with parameters reported by the device itself
+pub(crate) fn initialize(
+    handle: &DeviceHandle<GlobalContext>,
+    device_info: &mut DeviceInfo,
+    interface: u8,
+) {
+    match device_info.protocol {
+        Protocol::Generic => {
+            if let Some(report) = &device_info.init_report {
+                match send_output_report(handle, interface, report) {
+                    Ok(_) => info!("Sent initialization report to {}.", device_info.id),
+                    Err(err) => warn!(
+                        "Failed to send initialization report to {}: {}.",
+                        device_info.id, err
+                    ),
+                }
+            }
+        }
+        Protocol::Huion => huion::initialize(handle, device_info),
+        Protocol::XpPen => xppen::initialize(handle, device_info, interface),
+    }
+}
+
+/// Sends a HID output report through a SET_REPORT control transfer, the first byte is the report ID
+fn send_output_report(
+    handle: &DeviceHandle<GlobalContext>,
+    interface: u8,
+    report: &[u8],
+) -> rusb::Result<usize> {
+    let report_id = report.first().copied().unwrap_or(0) as u16;
+    handle.write_control(
+        rusb::request_type(Direction::Out, RequestType::Class, Recipient::Interface),
+        REQUEST_SET_REPORT,
+        (REPORT_TYPE_OUTPUT << 8) | report_id,
+        interface as u16,
+        report,
+        OUTPUT_REPORT_TIMEOUT,
+    )
+}
@@ -0,0 +1,77 @@
+use log::{info, warn};
+use rusb::{DeviceHandle, GlobalContext};
+
+use super::send_output_report;
+use crate::device_info::DeviceInfo;
+
+/// Without this the tablet only sends reports through its mouse emulation interface,
+/// same magic as the UGEE v2 init in the kernel's hid-uclogic
+const INIT_REPORT: [u8; 10] = [0x02, 0xb0, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+
+fn init_report(device_info: &DeviceInfo) -> &[u8] {
+    device_info.init_report.as_deref().unwrap_or(&INIT_REPORT)
+}
+
+pub(super) fn initialize(
+    handle: &DeviceHandle<GlobalContext>,
+    device_info: &DeviceInfo,
+    interface: u8,
+) {
+    match send_output_report(handle, interface, init_report(device_info)) {
+        Ok(_) => info!("Switched {} into full report mode.", device_info.id),
+        Err(err) => warn!(
+            "Failed to send initialization report to {}: {}, reports may be missing.",
+            device_info.id, err
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{init_report, INIT_REPORT};
+    use crate::{
+        device_info::{builtin_raw_db, resolve_db},
+        interfaces::{Button, ButtonState::*, PartialUpdate, PenReport},
+        protocol::Protocol,
+        report::Decoder,
+    };
+
+    #[test]
+    fn init_report_is_the_ugee_v2_magic() {
+        assert_eq!(
+            INIT_REPORT,
+            [0x02, 0xb0, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn builtin_entries_decode_pen_packets() {
+        // pen touching at (0x1234, 0x0abc) with pressure 0x0400 and the lower button held
+        let packet = [
+            0x02, 0xa3, 0x34, 0x12, 0xbc, 0x0a, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let xppen: Vec<_> = resolve_db(&builtin_raw_db())
+            .into_iter()
+            .filter(|device_info| device_info.protocol == Protocol::XpPen)
+            .collect();
+        assert!(!xppen.is_empty());
+        for device_info in &xppen {
+            assert_eq!(init_report(device_info), INIT_REPORT);
+            let mut decoder = Decoder::new(device_info);
+            assert_eq!(
+                decoder.decode(&packet[..device_info.packet_length]),
+                vec![
+                    PartialUpdate::Pen(PenReport {
+                        position: (0x1234, 0x0abc),
+                        pressure: Some(0x0400),
+                        tilt: Some((0, 0)),
+                    }),
+                    PartialUpdate::Button(Button::PenPrimary, Press),
+                    PartialUpdate::Button(Button::PenSecondary, Release),
+                ],
+                "{}",
+                device_info.id
+            );
+        }
+    }
+}
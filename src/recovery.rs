@@ -0,0 +1,130 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+
+use crate::{
+    interfaces::{ConnectionState, DeviceID, DeviceState},
+    tablet_device::{ReadError, TabletDevice},
+};
+
+/// Consecutive I/O errors tolerated before resetting the device
+const IO_ERRORS_BEFORE_RESET: u32 = 3;
+/// Failed reads in a row before giving up on the device
+const MAX_ATTEMPTS: u32 = 10;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+/// How often the stop flag is checked while backing off
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Decides how to recover from read errors, backing off exponentially while they repeat
+#[derive(Debug, Default)]
+pub(crate) struct Recovery {
+    attempt: u32,
+    io_errors: u32,
+}
+
+impl Recovery {
+    /// Called after every successful read
+    pub(crate) fn succeeded(&mut self, device_state: &DeviceState) {
+        if self.attempt > 0 {
+            let mut device_state = device_state.write();
+            info!("Device {} recovered.", device_state.id);
+            device_state.connection = ConnectionState::Connected;
+        }
+        self.attempt = 0;
+        self.io_errors = 0;
+    }
+
+    /// Returns whether to keep reading from the device
+    pub(crate) fn handle(
+        &mut self,
+        tablet: &mut TabletDevice,
+        device_state: &DeviceState,
+        stop: &AtomicBool,
+        error: ReadError,
+    ) -> bool {
+        let id = tablet.device_info.id;
+        let result = match error {
+            ReadError::Idle => return true,
+            ReadError::Disconnected => return disconnected(id, device_state),
+            ReadError::Stalled => {
+                if !self.back_off(id, device_state, stop, &error) {
+                    return false;
+                }
+                warn!("Endpoint of {} stalled, clearing halt.", id);
+                tablet.clear_halt()
+            }
+            ReadError::Io(err) => {
+                if !self.back_off(id, device_state, stop, &error) {
+                    return false;
+                }
+                self.io_errors += 1;
+                if self.io_errors < IO_ERRORS_BEFORE_RESET {
+                    warn!("Failed to read from {}: {}, retrying.", id, err);
+                    return true;
+                }
+                self.io_errors = 0;
+                warn!(
+                    "Failed to read from {} {} times in a row, resetting.",
+                    id, IO_ERRORS_BEFORE_RESET
+                );
+                tablet.reset()
+            }
+        };
+        match result.map_err(ReadError::from) {
+            Ok(()) => true,
+            Err(ReadError::Disconnected) => disconnected(id, device_state),
+            Err(err) => {
+                warn!("Failed to recover {}: {}.", id, err);
+                true
+            }
+        }
+    }
+
+    /// Returns false once there have been too many attempts or the handler is stopped
+    fn back_off(
+        &mut self,
+        id: DeviceID,
+        device_state: &DeviceState,
+        stop: &AtomicBool,
+        error: &ReadError,
+    ) -> bool {
+        self.attempt += 1;
+        if self.attempt > MAX_ATTEMPTS {
+            error!(
+                "Device {} keeps failing ({}), giving up until it is reconnected.",
+                id, error
+            );
+            device_state.write().connection = ConnectionState::Disconnected;
+            return false;
+        }
+        device_state.write().connection = ConnectionState::Recovering {
+            attempt: self.attempt,
+        };
+        let deadline = Instant::now() + backoff(self.attempt);
+        while !stop.load(Ordering::Relaxed) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return true;
+            }
+            thread::sleep(remaining.min(STOP_CHECK_INTERVAL));
+        }
+        false
+    }
+}
+
+fn disconnected(id: DeviceID, device_state: &DeviceState) -> bool {
+    info!("Device {} is gone, waiting for it to be reconnected.", id);
+    device_state.write().connection = ConnectionState::Disconnected;
+    false
+}
+
+fn backoff(attempt: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(1 << (attempt - 1).min(16))
+        .min(MAX_BACKOFF)
+}
@@ -0,0 +1,533 @@
+use log::{debug, error, warn};
+use serde::Deserialize;
+
+use crate::{
+    device_info::DeviceInfo,
+    interfaces::{Button, PartialUpdate, PenReport, WheelDirection},
+    utils::SelectBit,
+    wheel::{WheelInfo, WheelKind},
+};
+
+/// Decoded values end up in `u32` and `i32`
+const MAX_FIELD_BITS: u32 = 32;
+
+/// Describes one kind of report a device sends and where its values live
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub(crate) struct ReportLayout {
+    /// Expected value of the first byte
+    pub(crate) report_id: Option<u8>,
+    pub(crate) discriminator: Option<Discriminator>,
+    pub(crate) x: Option<Field>,
+    pub(crate) y: Option<Field>,
+    pub(crate) pressure: Option<Field>,
+    pub(crate) tilt_x: Option<Field>,
+    pub(crate) tilt_y: Option<Field>,
+    /// Position is only reported while this is non-zero
+    pub(crate) in_range: Option<Field>,
+    pub(crate) buttons: Vec<ButtonField>,
+    pub(crate) wheel: Option<Field>,
+}
+
+/// Matches when `packet[byte] & mask == value`
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct Discriminator {
+    pub(crate) byte: usize,
+    #[serde(default = "default_mask")]
+    pub(crate) mask: u8,
+    pub(crate) value: u8,
+}
+
+fn default_mask() -> u8 {
+    u8::MAX
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct Field {
+    /// Byte offset of the first byte
+    pub(crate) offset: usize,
+    /// Bit offset from the least significant bit of the first byte
+    #[serde(default)]
+    pub(crate) bit: u8,
+    pub(crate) bits: u8,
+    #[serde(default)]
+    pub(crate) endian: Endian,
+    #[serde(default)]
+    pub(crate) signed: bool,
+    /// More significant bits stored elsewhere in the packet, placed above `bits`
+    #[serde(default)]
+    pub(crate) high: Option<Box<Field>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+pub(crate) enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(crate) struct ButtonField {
+    pub(crate) byte: usize,
+    pub(crate) bit: u8,
+    pub(crate) button: Button,
+}
+
+impl ReportLayout {
+    pub(crate) fn matches(&self, packet: &[u8]) -> bool {
+        if let Some(report_id) = self.report_id {
+            if packet.first() != Some(&report_id) {
+                return false;
+            }
+        }
+        if let Some(discriminator) = &self.discriminator {
+            match packet.get(discriminator.byte) {
+                Some(byte) if byte & discriminator.mask == discriminator.value => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    fn fields(&self) -> [(&'static str, &Option<Field>); 7] {
+        [
+            ("x", &self.x),
+            ("y", &self.y),
+            ("pressure", &self.pressure),
+            ("tilt_x", &self.tilt_x),
+            ("tilt_y", &self.tilt_y),
+            ("in_range", &self.in_range),
+            ("wheel", &self.wheel),
+        ]
+    }
+
+    /// Last byte, exclusive, touched by any field of this layout
+    pub(crate) fn span(&self) -> usize {
+        let field_span = self
+            .fields()
+            .into_iter()
+            .filter_map(|(_, field)| field.as_ref())
+            .map(Field::span);
+        let button_span = self.buttons.iter().map(|button| button.byte + 1);
+        let discriminator_span = self
+            .discriminator
+            .iter()
+            .map(|discriminator| discriminator.byte + 1);
+        field_span
+            .chain(button_span)
+            .chain(discriminator_span)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Rejects bit positions the decoder can't extract without overflowing
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match self.errors().into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (name, field) in self.fields() {
+            if let Some(Err(err)) = field.as_ref().map(Field::validate) {
+                errors.push(format!("{} {}", name, err));
+            }
+        }
+        for button_field in &self.buttons {
+            if button_field.bit >= 8 {
+                errors.push(format!(
+                    "button {} has bit {}, which is not below 8",
+                    button_field.button, button_field.bit
+                ));
+            }
+        }
+        errors
+    }
+}
+
+impl Field {
+    fn validate(&self) -> Result<(), String> {
+        if self.bit >= 8 {
+            return Err(format!("has bit {}, which is not below 8", self.bit));
+        }
+        if (self.bit as u32 + self.bits as u32) > u64::BITS {
+            return Err(format!(
+                "has bit {} and bits {}, which exceed 64 bits",
+                self.bit, self.bits
+            ));
+        }
+        if self.total_bits() > MAX_FIELD_BITS {
+            return Err(format!(
+                "is {} bits wide including high, which exceeds {} bits",
+                self.total_bits(),
+                MAX_FIELD_BITS
+            ));
+        }
+        match &self.high {
+            Some(high) => high.validate().map_err(|err| format!("high {}", err)),
+            None => Ok(()),
+        }
+    }
+
+    fn byte_count(&self) -> usize {
+        (self.bit as usize + self.bits as usize).div_ceil(8)
+    }
+
+    pub(crate) fn span(&self) -> usize {
+        let high_span = self.high.as_ref().map_or(0, |high| high.span());
+        (self.offset + self.byte_count()).max(high_span)
+    }
+
+    fn total_bits(&self) -> u32 {
+        let high_bits = self.high.as_ref().map_or(0, |high| high.total_bits());
+        self.bits as u32 + high_bits
+    }
+
+    fn extract_unsigned(&self, packet: &[u8]) -> Option<u64> {
+        let bytes = packet.get(self.offset..self.offset + self.byte_count())?;
+        let raw = match self.endian {
+            Endian::Little => bytes
+                .iter()
+                .rev()
+                .fold(0u64, |value, byte| (value << 8) | *byte as u64),
+            Endian::Big => bytes
+                .iter()
+                .fold(0u64, |value, byte| (value << 8) | *byte as u64),
+        };
+        let value = (raw >> self.bit) & ((1u64 << self.bits.min(63)) - 1);
+        match &self.high {
+            Some(high) => Some(value | (high.extract_unsigned(packet)? << self.bits.min(63))),
+            None => Some(value),
+        }
+    }
+
+    pub(crate) fn extract(&self, packet: &[u8]) -> Option<i64> {
+        let value = self.extract_unsigned(packet)?;
+        let bits = self.total_bits().min(u64::BITS);
+        if self.signed && bits > 0 {
+            // moves the sign bit to the top and shifts it back in
+            let unused = u64::BITS - bits;
+            Some(((value << unused) as i64) >> unused)
+        } else {
+            Some(value as i64)
+        }
+    }
+}
+
+/// Turns raw packets into updates according to the layouts in `DeviceInfo`
+pub(crate) struct Decoder {
+    reports: Vec<ReportLayout>,
+    button_available: Vec<Button>,
+    wheel: Option<WheelInfo>,
+    last_wheel_position: Option<i64>,
+}
+
+impl Decoder {
+    pub(crate) fn new(device_info: &DeviceInfo) -> Decoder {
+        if device_info.reports.is_empty() {
+            warn!(
+                "Device {} has no report layouts, no input will be decoded.",
+                device_info.id
+            );
+        }
+        for layout in &device_info.reports {
+            if layout.span() > device_info.packet_length {
+                warn!(
+                    "A report layout of {} reaches beyond the packet length {}.",
+                    device_info.id, device_info.packet_length
+                );
+            }
+        }
+        let reports = device_info
+            .reports
+            .iter()
+            .filter(|layout| match layout.validate() {
+                Ok(()) => true,
+                Err(err) => {
+                    error!("Ignoring a report layout of {}: {}.", device_info.id, err);
+                    false
+                }
+            })
+            .cloned()
+            .collect();
+        Decoder {
+            reports,
+            button_available: device_info.button_available.clone(),
+            wheel: device_info.wheel.clone(),
+            last_wheel_position: None,
+        }
+    }
+
+    pub(crate) fn decode(&mut self, packet: &[u8]) -> Vec<PartialUpdate> {
+        let mut updates = Vec::new();
+        let Some(layout) = self.reports.iter().find(|layout| layout.matches(packet)) else {
+            return updates;
+        };
+
+        let x = layout.x.as_ref().and_then(|field| field.extract(packet));
+        let y = layout.y.as_ref().and_then(|field| field.extract(packet));
+        let in_range = layout
+            .in_range
+            .as_ref()
+            .and_then(|field| field.extract(packet))
+            .is_none_or(|in_range| in_range != 0);
+        if let (Some(x), Some(y), true) = (x, y, in_range) {
+            let pressure = layout
+                .pressure
+                .as_ref()
+                .and_then(|field| field.extract(packet));
+            let tilt_x = layout
+                .tilt_x
+                .as_ref()
+                .and_then(|field| field.extract(packet));
+            let tilt_y = layout
+                .tilt_y
+                .as_ref()
+                .and_then(|field| field.extract(packet));
+            updates.push(PartialUpdate::Pen(PenReport {
+                position: (x.max(0) as u32, y.max(0) as u32),
+                pressure: pressure.map(|pressure| pressure.max(0) as u32),
+                tilt: tilt_x
+                    .zip(tilt_y)
+                    .map(|(tilt_x, tilt_y)| (tilt_x as i32, tilt_y as i32)),
+            }));
+        }
+
+        for button_field in &layout.buttons {
+            if !self.button_available.contains(&button_field.button) {
+                continue;
+            }
+            if let Some(byte) = packet.get(button_field.byte) {
+                let state = byte.is_bit_set(button_field.bit).into();
+                updates.push(PartialUpdate::Button(button_field.button, state));
+            }
+        }
+
+        if let Some(value) = layout
+            .wheel
+            .as_ref()
+            .and_then(|field| field.extract(packet))
+        {
+            let wheel_updates = self
+                .decode_wheel(value)
+                .into_iter()
+                .map(PartialUpdate::Wheel);
+            updates.extend(wheel_updates);
+        }
+
+        updates
+    }
+
+    fn decode_wheel(&mut self, value: i64) -> Vec<WheelDirection> {
+        let Some(wheel_info) = &self.wheel else {
+            debug!("Received wheel report from a device without a wheel, ignoring.");
+            return Vec::new();
+        };
+        match wheel_info.kind {
+            WheelKind::Relative => match value {
+                1 => vec![WheelDirection::Clockwise],
+                2 => vec![WheelDirection::CounterClockwise],
+                _ => Vec::new(),
+            },
+            WheelKind::Absolute => {
+                if (value == 0) || (wheel_info.positions == 0) {
+                    self.last_wheel_position = None;
+                    return Vec::new();
+                }
+                let Some(last_position) = self.last_wheel_position.replace(value) else {
+                    return Vec::new();
+                };
+                // take the shorter way around the ring
+                let positions = wheel_info.positions as i64;
+                let delta = (value - last_position).rem_euclid(positions);
+                if delta == 0 {
+                    Vec::new()
+                } else if delta <= positions / 2 {
+                    vec![WheelDirection::Clockwise; delta as usize]
+                } else {
+                    vec![WheelDirection::CounterClockwise; (positions - delta) as usize]
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{Decoder, Field};
+    use crate::{
+        device_info::{builtin_raw_db, resolve_db, DeviceInfo},
+        interfaces::{Button, ButtonState::*, DeviceID, PartialUpdate, PenReport, WheelDirection},
+    };
+
+    fn builtin(id: &str) -> DeviceInfo {
+        let id: DeviceID = id.parse().unwrap();
+        resolve_db(&builtin_raw_db())
+            .into_iter()
+            .find(|device_info| device_info.id == id)
+            .unwrap()
+    }
+
+    /// Updates of the eight pad buttons with `pressed` held
+    fn pad(pressed: &[u32]) -> Vec<PartialUpdate> {
+        (0..8)
+            .map(|index| {
+                PartialUpdate::Button(Button::Tablet(index), pressed.contains(&index).into())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn huion_q620m_pen() {
+        let device_info = builtin("256c:006d");
+        assert_eq!(device_info.packet_length, 12);
+        let mut decoder = Decoder::new(&device_info);
+
+        // x reaches into its high byte, tilted, with the lower button held
+        let packet = [
+            0x08, 0x82, 0x50, 0xc3, 0x20, 0x4e, 0x00, 0x10, 0x01, 0x00, 0xfb, 0x05,
+        ];
+        assert_eq!(
+            decoder.decode(&packet),
+            vec![
+                PartialUpdate::Pen(PenReport {
+                    position: (115536, 20000),
+                    pressure: Some(4096),
+                    tilt: Some((-5, 5)),
+                }),
+                PartialUpdate::Button(Button::PenPrimary, Press),
+                PartialUpdate::Button(Button::PenSecondary, Release),
+            ]
+        );
+
+        // the template has no pad layout, and other report IDs are not for the pen
+        let packet = [
+            0x08, 0xe0, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(decoder.decode(&packet), vec![]);
+        let packet = [
+            0x07, 0x82, 0x50, 0xc3, 0x20, 0x4e, 0x00, 0x10, 0x01, 0x00, 0xfb, 0x05,
+        ];
+        assert_eq!(decoder.decode(&packet), vec![]);
+    }
+
+    #[test]
+    fn xppen_deco01v2_pen() {
+        let device_info = builtin("28bd:0905");
+        assert_eq!(device_info.packet_length, 10);
+        let mut decoder = Decoder::new(&device_info);
+
+        // hovering
+        let packet = [0x02, 0xa0, 0x10, 0x27, 0x20, 0x4e, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(
+            decoder.decode(&packet),
+            vec![
+                PartialUpdate::Pen(PenReport {
+                    position: (10000, 20000),
+                    pressure: Some(0),
+                    tilt: Some((0, 0)),
+                }),
+                PartialUpdate::Button(Button::PenPrimary, Release),
+                PartialUpdate::Button(Button::PenSecondary, Release),
+            ]
+        );
+
+        // touching and tilted, with the primary button held
+        let packet = [0x02, 0xa3, 0xc4, 0x09, 0x88, 0x13, 0x00, 0x10, 0x05, 0xfb];
+        assert_eq!(
+            decoder.decode(&packet),
+            vec![
+                PartialUpdate::Pen(PenReport {
+                    position: (2500, 5000),
+                    pressure: Some(4096),
+                    tilt: Some((5, -5)),
+                }),
+                PartialUpdate::Button(Button::PenPrimary, Press),
+                PartialUpdate::Button(Button::PenSecondary, Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn xppen_deco01v2_pad() {
+        let mut decoder = Decoder::new(&builtin("28bd:0905"));
+        let packet = [0x02, 0xf0, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(decoder.decode(&packet), pad(&[0, 2]));
+    }
+
+    #[test]
+    fn xppen_decopro_s() {
+        let device_info = builtin("28bd:0909");
+        assert_eq!(device_info.packet_length, 12);
+        let mut decoder = Decoder::new(&device_info);
+
+        let packet = [
+            0x02, 0xa4, 0x10, 0x27, 0x20, 0x4e, 0xff, 0x1f, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(
+            decoder.decode(&packet),
+            vec![
+                PartialUpdate::Pen(PenReport {
+                    position: (10000, 20000),
+                    pressure: Some(8191),
+                    tilt: Some((0, 0)),
+                }),
+                PartialUpdate::Button(Button::PenPrimary, Release),
+                PartialUpdate::Button(Button::PenSecondary, Press),
+            ]
+        );
+
+        let packet = [
+            0x02, 0xf0, 0x80, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut expected = pad(&[7]);
+        expected.push(PartialUpdate::Button(Button::Wheel, Press));
+        assert_eq!(decoder.decode(&packet), expected);
+
+        // dial turned clockwise, then counter clockwise
+        for (value, direction) in [
+            (0x01, WheelDirection::Clockwise),
+            (0x02, WheelDirection::CounterClockwise),
+        ] {
+            let packet = [
+                0x02, 0xf0, 0x00, 0x00, 0x00, 0x00, 0x00, value, 0x00, 0x00, 0x00, 0x00,
+            ];
+            let mut expected = pad(&[]);
+            expected.push(PartialUpdate::Button(Button::Wheel, Release));
+            expected.push(PartialUpdate::Wheel(direction));
+            assert_eq!(decoder.decode(&packet), expected);
+        }
+    }
+
+    fn field(value: Value) -> Field {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn signed_fields() {
+        let tilt = field(json!({ "offset": 0, "bits": 8, "signed": true }));
+        assert_eq!(tilt.extract(&[0xfb]), Some(-5));
+        assert_eq!(tilt.extract(&[0x05]), Some(5));
+        let wide = field(json!({
+            "offset": 0, "bits": 16, "signed": true, "high": { "offset": 2, "bits": 16 }
+        }));
+        assert!(wide.validate().is_ok());
+        assert_eq!(wide.extract(&[0xff, 0xff, 0xff, 0xff]), Some(-1));
+        assert_eq!(
+            wide.extract(&[0x00, 0x00, 0x00, 0x80]),
+            Some(i32::MIN as i64)
+        );
+    }
+
+    #[test]
+    fn fields_wider_than_32_bits_are_rejected() {
+        let wide = field(json!({
+            "offset": 0, "bits": 32, "signed": true, "high": { "offset": 4, "bits": 31 }
+        }));
+        assert!(wide.validate().is_err());
+    }
+}
@@ -1,24 +1,47 @@
-use crate::{device_info::DeviceInfo, utils::LogExpect};
-use log::{debug, error};
+use crate::{device_info::DeviceInfo, protocol, utils::LogExpect};
+use log::{debug, error, warn};
 use rusb::{DeviceHandle, GlobalContext};
-use std::{thread, time::Duration};
+use std::{fmt::Display, thread, time::Duration};
 
-const READ_TIMEOUT: Duration = Duration::new(10, 0);
+/// Short enough for handlers to notice when they are stopped
+const READ_TIMEOUT: Duration = Duration::from_millis(200);
 const RESET_WAIT_TIMEOUT: Duration = Duration::from_millis(50);
 
 pub(crate) struct TabletDevice {
     device: DeviceHandle<GlobalContext>,
+    interface: u8,
     endpoint: u8,
+    claimed: Vec<ClaimedInterface>,
     pub(crate) device_info: DeviceInfo,
+    /// Bumped whenever `device_info` may have been updated by the device, after a reset
+    pub(crate) generation: usize,
+}
+
+struct ClaimedInterface {
+    number: u8,
+    /// Only drivers detached by us are reattached
+    kernel_driver_detached: bool,
 }
 
 impl TabletDevice {
+    /// Fails if there is nothing to decode, which releases the claimed interfaces again
     pub(crate) fn new(
-        mut handle: DeviceHandle<GlobalContext>,
+        handle: DeviceHandle<GlobalContext>,
         device_info: &DeviceInfo,
-    ) -> TabletDevice {
+    ) -> Result<TabletDevice, String> {
         let device = handle.device();
 
+        // constructed early so that interfaces are released even if the setup below panics
+        let mut tablet = TabletDevice {
+            device: handle,
+            interface: 0,
+            endpoint: 0,
+            claimed: Vec::new(),
+            device_info: device_info.clone(),
+            generation: 0,
+        };
+        let handle = &mut tablet.device;
+
         // source: https://github.com/DavidBM/huion-linux-driver-rust
 
         let config_descriptor = device
@@ -52,7 +75,7 @@ impl TabletDevice {
 
         debug!("Finding interfaces...");
 
-        let mut available_endpoints: Vec<u8> = vec![];
+        let mut available_endpoints: Vec<(u8, u8)> = vec![];
 
         for interface in config_descriptor.interfaces() {
             let interface_number = interface.number();
@@ -65,9 +88,12 @@ impl TabletDevice {
             let endpoint_descriptors: Vec<rusb::EndpointDescriptor> =
                 interface_descriptor.endpoint_descriptors().collect();
 
-            let endpoint_descriptor = &endpoint_descriptors[0];
-
-            available_endpoints.push(endpoint_descriptor.address());
+            if let Some(endpoint_descriptor) = endpoint_descriptors
+                .iter()
+                .find(|endpoint| endpoint.direction() == rusb::Direction::In)
+            {
+                available_endpoints.push((interface_number, endpoint_descriptor.address()));
+            }
 
             debug!("Found interface: {:?}", interface.number());
 
@@ -90,33 +116,77 @@ impl TabletDevice {
                     });
             }
 
-            handle
-                .claim_interface(interface.number())
-                .unwrap_or_else(|_| {
-                    error!("Error claiming interface: {}", interface_number);
-                    panic!("Exiting...");
-                });
+            let claimed = handle.claim_interface(interface.number());
+            // recorded before checking the result so that the driver is reattached either way
+            tablet.claimed.push(ClaimedInterface {
+                number: interface_number,
+                kernel_driver_detached: is_kernel_active,
+            });
+            claimed.unwrap_or_else(|_| {
+                error!("Error claiming interface: {}", interface_number);
+                panic!("Exiting...");
+            });
 
             debug!("Claimed interface {}", interface_number);
         }
 
-        let endpoint = available_endpoints[0];
-
-        TabletDevice {
-            device: handle,
-            endpoint,
-            device_info: device_info.clone(),
+        let device_info = &mut tablet.device_info;
+        if device_info.reports.is_empty() {
+            let interfaces: Vec<u8> = available_endpoints
+                .iter()
+                .map(|(interface_number, _)| *interface_number)
+                .filter(|interface_number| {
+                    device_info
+                        .interface
+                        .is_none_or(|interface| interface == *interface_number)
+                })
+                .collect();
+            protocol::hid::discover(handle, device_info, &interfaces);
+        }
+        // a zero length buffer would make every read return immediately
+        if device_info.packet_length == 0 {
+            return Err(format!(
+                "Device {} has no report layouts and no packet length",
+                device_info.id
+            ));
         }
+
+        let (interface, endpoint) = match device_info.interface {
+            Some(interface) => available_endpoints
+                .iter()
+                .find(|(interface_number, _)| *interface_number == interface)
+                .copied()
+                .log_expect(format!("Interface {} has no input endpoint.", interface)),
+            None => available_endpoints[0],
+        };
+        debug!(
+            "Reading from endpoint {:#04x} of interface {}",
+            endpoint, interface
+        );
+
+        protocol::initialize(handle, device_info, interface);
+
+        tablet.interface = interface;
+        tablet.endpoint = endpoint;
+        Ok(tablet)
     }
 
-    pub(crate) fn read(&self, buffer: &mut [u8]) -> Option<()> {
-        match self.device.read_bulk(self.endpoint, buffer, READ_TIMEOUT) {
-            Ok(_) => Some(()),
-            Err(err) => {
-                debug!("Failed to read from device: {}.", err);
-                None
-            }
-        }
+    pub(crate) fn read(&self, buffer: &mut [u8]) -> Result<usize, ReadError> {
+        self.device
+            .read_bulk(self.endpoint, buffer, READ_TIMEOUT)
+            .map_err(ReadError::from)
+    }
+
+    pub(crate) fn clear_halt(&mut self) -> rusb::Result<()> {
+        self.device.clear_halt(self.endpoint)
+    }
+
+    /// Claimed interfaces survive the reset, but the device has to be initialized again
+    pub(crate) fn reset(&mut self) -> rusb::Result<()> {
+        self.device.reset()?;
+        protocol::initialize(&self.device, &mut self.device_info, self.interface);
+        self.generation += 1;
+        Ok(())
     }
 
     // pub(crate) async fn async_read(self, buffer: &'static mut Vec<u8>) -> Option<()> {
@@ -133,3 +203,58 @@ impl TabletDevice {
     //     }
     // }
 }
+
+#[derive(Debug)]
+pub(crate) enum ReadError {
+    /// Nothing was sent before the timeout
+    Idle,
+    /// The endpoint is halted
+    Stalled,
+    /// The device is gone and will show up again under a new address, if at all
+    Disconnected,
+    Io(rusb::Error),
+}
+
+impl From<rusb::Error> for ReadError {
+    fn from(value: rusb::Error) -> Self {
+        match value {
+            rusb::Error::Timeout => Self::Idle,
+            rusb::Error::Pipe => Self::Stalled,
+            rusb::Error::NoDevice | rusb::Error::NotFound => Self::Disconnected,
+            err => Self::Io(err),
+        }
+    }
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Idle => write!(f, "no input"),
+            Self::Stalled => write!(f, "endpoint stalled"),
+            Self::Disconnected => write!(f, "device disconnected"),
+            Self::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Drop for TabletDevice {
+    fn drop(&mut self) {
+        for interface in &self.claimed {
+            match self.device.release_interface(interface.number) {
+                Ok(()) => debug!("Released interface {}", interface.number),
+                Err(err) => debug!("Failed to release interface {}: {}", interface.number, err),
+            }
+            if interface.kernel_driver_detached {
+                match self.device.attach_kernel_driver(interface.number) {
+                    Ok(()) => debug!("Reattached kernel driver to interface {}", interface.number),
+                    // nothing to reattach to once the device is gone
+                    Err(rusb::Error::NoDevice) => {}
+                    Err(err) => warn!(
+                        "Failed to reattach kernel driver to interface {} of {}: {}.",
+                        interface.number, self.device_info.id, err
+                    ),
+                }
+            }
+        }
+    }
+}
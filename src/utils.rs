@@ -1,6 +1,8 @@
 use log::error;
 
-use crate::{device_info::DeviceInfo, interfaces::DeviceID, tablet_device::TabletDevice};
+use crate::{
+    device_info::DeviceInfo, interfaces::DeviceID, protocol::Protocol, tablet_device::TabletDevice,
+};
 
 pub(crate) fn list_devices() {
     println!("Printing all available hid devices:");
@@ -26,17 +28,28 @@ pub(crate) fn print_huion_device_input() {
     let id = DeviceID { vid: VID, pid: PID };
     let device_info = DeviceInfo {
         id,
+        matcher: Default::default(),
+        protocol: Protocol::Huion,
+        interface: None,
+        init_report: None,
         width: 2000f32,
         height: 1000f32,
         button_available: Vec::new(),
-        wheel: true,
+        wheel: Some(Default::default()),
         packet_length: 12,
+        reports: Vec::new(),
+        pressure_max: 255f32,
+        resolution: None,
+        source: Default::default(),
+        origin: String::new(),
+        provided: Default::default(),
     };
     let device = rusb::open_device_with_vid_pid(VID, PID).expect("Failed to open test device.");
-    let tablet = TabletDevice::new(device, &device_info);
+    let tablet =
+        TabletDevice::new(device, &device_info).log_expect("Failed to set up test device.");
     let mut buffer = [0; 12];
     loop {
-        tablet.read(&mut buffer);
+        let _ = tablet.read(&mut buffer);
         println!("Read data: {:?}", buffer);
     }
 }
@@ -0,0 +1,129 @@
+use log::info;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    interfaces::WheelDirection,
+    platform::{InputAction, VirtualInputDevice},
+    utils::LogExpect,
+};
+
+static ZOOM_IN: Lazy<InputAction> =
+    Lazy::new(|| "Ctrl+Equal".parse().log_expect("Invalid zoom in chord."));
+static ZOOM_OUT: Lazy<InputAction> =
+    Lazy::new(|| "Ctrl+Minus".parse().log_expect("Invalid zoom out chord."));
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub(crate) struct WheelInfo {
+    #[serde(default)]
+    pub(crate) kind: WheelKind,
+    /// Number of positions on an absolute ring
+    #[serde(default)]
+    pub(crate) positions: u8,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+pub(crate) enum WheelKind {
+    /// Reports a tick per step, 1 for clockwise and 2 for counter-clockwise
+    #[default]
+    Relative,
+    /// Reports the touched position on a ring, 0 when released
+    Absolute,
+}
+
+/// Accepts the legacy `true`/`false` as well as a full wheel description
+pub(crate) fn deserialize_wheel<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<WheelInfo>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum WheelField {
+        Flag(bool),
+        Info(WheelInfo),
+    }
+
+    Ok(match Option::<WheelField>::deserialize(deserializer)? {
+        None | Some(WheelField::Flag(false)) => None,
+        Some(WheelField::Flag(true)) => Some(WheelInfo::default()),
+        Some(WheelField::Info(info)) => Some(info),
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub(crate) struct WheelConfig {
+    /// Cycled through by pressing the wheel button
+    pub(crate) modes: Vec<WheelMode>,
+}
+
+impl Default for WheelConfig {
+    fn default() -> WheelConfig {
+        WheelConfig {
+            modes: vec![WheelMode::Scroll],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) enum WheelMode {
+    Scroll,
+    Zoom,
+    Actions {
+        clockwise: InputAction,
+        counter_clockwise: InputAction,
+    },
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct WheelState {
+    mode: usize,
+}
+
+impl WheelState {
+    pub(crate) fn cycle(&mut self, wheel_config: &WheelConfig) {
+        if wheel_config.modes.is_empty() {
+            return;
+        }
+        self.mode = (self.mode + 1) % wheel_config.modes.len();
+        info!(
+            "Switched to wheel mode {:?}.",
+            wheel_config.modes[self.mode]
+        );
+    }
+
+    pub(crate) fn dispatch(
+        &self,
+        vinput: &mut impl VirtualInputDevice,
+        wheel_config: &WheelConfig,
+        direction: &WheelDirection,
+    ) {
+        let Some(mode) = wheel_config
+            .modes
+            .get(self.mode)
+            .or_else(|| wheel_config.modes.first())
+        else {
+            return;
+        };
+        let clockwise = matches!(direction, WheelDirection::Clockwise);
+        match mode {
+            WheelMode::Scroll => vinput.submit_scroll(if clockwise { -1 } else { 1 }),
+            WheelMode::Zoom => {
+                let action = if clockwise { &ZOOM_IN } else { &ZOOM_OUT };
+                vinput.submit_action(action, true);
+                vinput.submit_action(action, false);
+            }
+            WheelMode::Actions {
+                clockwise: clockwise_action,
+                counter_clockwise: counter_clockwise_action,
+            } => {
+                let action = if clockwise {
+                    clockwise_action
+                } else {
+                    counter_clockwise_action
+                };
+                vinput.submit_action(action, true);
+                vinput.submit_action(action, false);
+            }
+        }
+    }
+}
@@ -0,0 +1,618 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    device_info::DeviceInfo,
+    interfaces::DeviceID,
+    protocol::Protocol,
+    tablet_device::TabletDevice,
+    utils::{LogExpect, SelectBit},
+};
+
+const STEP_DURATION: Duration = Duration::from_secs(5);
+const MAX_PACKET_LENGTH: usize = 64;
+/// Smallest range a tilt byte has to cover to count as tilt
+const MIN_TILT_RANGE: i32 = 10;
+/// Share of a step's packets that have to carry a button bit
+const MIN_BUTTON_SHARE: f32 = 0.5;
+const PEN_BUTTONS: [&str; 2] = ["PenPrimary", "PenSecondary"];
+
+/// Raw packets recorded during each step of the wizard
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub(crate) struct Session {
+    /// Pen away, nothing pressed
+    pub(crate) idle: Vec<Vec<u8>>,
+    pub(crate) top_left: Vec<Vec<u8>>,
+    pub(crate) top_right: Vec<Vec<u8>>,
+    pub(crate) bottom_right: Vec<Vec<u8>>,
+    pub(crate) pressure: Vec<Vec<u8>>,
+    pub(crate) tilt: Vec<Vec<u8>>,
+    /// One recording per pen button, primary first
+    pub(crate) pen_buttons: Vec<Vec<Vec<u8>>>,
+    /// One recording per pad button
+    pub(crate) pad_buttons: Vec<Vec<Vec<u8>>>,
+}
+
+/// How to recognize the packets of one report
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ReportMatch {
+    report_id: u8,
+    /// Mask and value of the second byte
+    discriminator: Option<(u8, u8)>,
+}
+
+impl ReportMatch {
+    fn matches(&self, packet: &[u8]) -> bool {
+        packet.first() == Some(&self.report_id)
+            && self
+                .discriminator
+                .is_none_or(|(mask, value)| packet.get(1).is_some_and(|byte| byte & mask == value))
+    }
+
+    fn filter<'a>(&self, packets: &'a [Vec<u8>]) -> Vec<&'a Vec<u8>> {
+        packets
+            .iter()
+            .filter(|packet| self.matches(packet))
+            .collect()
+    }
+
+    fn to_json(self) -> Map<String, Value> {
+        let mut layout = Map::new();
+        layout.insert("report_id".to_string(), json!(self.report_id));
+        if let Some((mask, value)) = self.discriminator {
+            layout.insert(
+                "discriminator".to_string(),
+                json!({ "byte": 1, "mask": mask, "value": value }),
+            );
+        }
+        layout
+    }
+}
+
+/// Guides through the recording steps, or replays a recorded session, and writes the result
+pub(crate) fn run_wizard(
+    id: DeviceID,
+    protocol: Protocol,
+    interface: Option<u8>,
+    replay: Option<&Path>,
+    record: Option<&Path>,
+    output: &Path,
+) -> bool {
+    let session = match replay {
+        Some(path) => {
+            let content = fs::read(path).log_expect(format!("Failed to read {}.", path.display()));
+            serde_json::from_slice::<Session>(&content)
+                .log_expect(format!("{} is not a recorded session.", path.display()))
+        }
+        None => record_session(id, protocol, interface),
+    };
+    if let Some(path) = record {
+        let content = serde_json::to_string(&session).log_expect("Failed to serialize session.");
+        fs::write(path, content).log_expect(format!("Failed to write {}.", path.display()));
+        info!("Recorded session saved to {}.", path.display());
+    }
+
+    let mut entry = match analyse(id, &session) {
+        Ok(entry) => entry,
+        Err(err) => {
+            error!("Failed to infer a device definition: {}", err);
+            return false;
+        }
+    };
+    if protocol != Protocol::Generic {
+        entry.insert("protocol".to_string(), json!(format!("{:?}", protocol)));
+    }
+    if let Some(interface) = interface {
+        entry.insert("interface".to_string(), json!(interface));
+    }
+
+    let path = output.join(format!("{:04x}-{:04x}.json", id.vid, id.pid));
+    if path.exists() {
+        error!(
+            "{} already exists, remove it first or pass a different --output.",
+            path.display()
+        );
+        return false;
+    }
+    let content = serde_json::to_string_pretty(&entry).log_expect("Failed to serialize entry.");
+    fs::create_dir_all(output).log_expect("Failed to create the output directory.");
+    fs::write(&path, content + "\n").log_expect(format!("Failed to write {}.", path.display()));
+    println!("Wrote {}, check it with `rablet db check`.", path.display());
+    println!("Wheels aren't detected yet, add them by hand if the tablet has one.");
+    true
+}
+
+fn record_session(id: DeviceID, protocol: Protocol, interface: Option<u8>) -> Session {
+    let handle = rusb::open_device_with_vid_pid(id.vid, id.pid)
+        .log_expect(format!("Failed to open device {}.", id));
+    let device_info = DeviceInfo {
+        id,
+        matcher: Default::default(),
+        protocol,
+        interface,
+        init_report: None,
+        height: 0.0,
+        width: 0.0,
+        button_available: Vec::new(),
+        wheel: None,
+        packet_length: MAX_PACKET_LENGTH,
+        // a dummy layout so the report descriptor isn't used, the raw packets are what matters here
+        reports: vec![Default::default()],
+        pressure_max: 0.0,
+        resolution: None,
+        source: Default::default(),
+        origin: String::new(),
+        provided: Default::default(),
+    };
+    let tablet = TabletDevice::new(handle, &device_info)
+        .log_expect(format!("Failed to set up device {}.", id));
+
+    let mut session = Session {
+        idle: record_step(&tablet, "Keep the pen away from the tablet."),
+        top_left: record_step(
+            &tablet,
+            "Hover the pen over the top left corner of the area.",
+        ),
+        top_right: record_step(
+            &tablet,
+            "Hover the pen over the top right corner of the area.",
+        ),
+        bottom_right: record_step(
+            &tablet,
+            "Hover the pen over the bottom right corner of the area.",
+        ),
+        pressure: record_step(
+            &tablet,
+            "Press the pen tip with slowly increasing pressure, up to the maximum.",
+        ),
+        tilt: record_step(&tablet, "Hover the pen and tilt it in every direction."),
+        ..Default::default()
+    };
+    let pen_buttons = prompt("Number of pen buttons:").parse().unwrap_or(0);
+    for button in PEN_BUTTONS.iter().take(pen_buttons) {
+        session.pen_buttons.push(record_step(
+            &tablet,
+            &format!("Hover the pen and hold its {} button.", button),
+        ));
+    }
+    let pad_buttons = prompt("Number of pad buttons:").parse().unwrap_or(0);
+    for index in 0..pad_buttons {
+        session.pad_buttons.push(record_step(
+            &tablet,
+            &format!("Hold pad button {} and nothing else.", index + 1),
+        ));
+    }
+    session
+}
+
+fn prompt(message: &str) -> String {
+    println!("{}", message);
+    let mut buffer = String::new();
+    io::stdin()
+        .read_line(&mut buffer)
+        .log_expect("Failed to get stdin input.");
+    buffer.trim().to_string()
+}
+
+fn record_step(tablet: &TabletDevice, instruction: &str) -> Vec<Vec<u8>> {
+    prompt(&format!(
+        "{} Press Enter to record for {} seconds.",
+        instruction,
+        STEP_DURATION.as_secs()
+    ));
+    let mut packets = Vec::new();
+    let mut buffer = [0; MAX_PACKET_LENGTH];
+    let start = Instant::now();
+    while start.elapsed() < STEP_DURATION {
+        if let Ok(length) = tablet.read(&mut buffer) {
+            packets.push(buffer[..length].to_vec());
+        }
+    }
+    println!("Recorded {} packets.", packets.len());
+    packets
+}
+
+fn le16(packet: &[u8], offset: usize) -> Option<u32> {
+    Some(u16::from_le_bytes([*packet.get(offset)?, *packet.get(offset + 1)?]) as u32)
+}
+
+fn most_common<T: Ord + Copy>(values: impl Iterator<Item = T>) -> Option<T> {
+    let mut counts = BTreeMap::new();
+    for value in values {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(value, _)| value)
+}
+
+/// Report ID and high nibble of the second byte shared by all `packets`
+fn infer_match(packets: &[&Vec<u8>]) -> Option<ReportMatch> {
+    let report_id = most_common(packets.iter().filter_map(|packet| packet.first().copied()))?;
+    let packets: Vec<&&Vec<u8>> = packets
+        .iter()
+        .filter(|packet| packet.first() == Some(&report_id))
+        .collect();
+    let first = packets.first()?.get(1).copied();
+    let discriminator = first.and_then(|first| {
+        let constant = packets
+            .iter()
+            .all(|packet| packet.get(1).map(|byte| byte & 0xf0) == Some(first & 0xf0));
+        constant.then_some((0xf0, first & 0xf0))
+    });
+    Some(ReportMatch {
+        report_id,
+        discriminator,
+    })
+}
+
+/// Offset of the 16 bit field separating `low` from `high` the most, while staying
+/// steady in `steady`, where the pen is held in one place
+fn separating_field(
+    low: &[&Vec<u8>],
+    high: &[&Vec<u8>],
+    steady: &[&Vec<u8>],
+    used: &[usize],
+) -> Option<usize> {
+    let length = low.iter().chain(high).map(|packet| packet.len()).min()?;
+    (2..length.saturating_sub(1))
+        .filter(|offset| !used.contains(offset) && !used.contains(&(offset + 1)))
+        .filter_map(|offset| {
+            let low_max = low.iter().filter_map(|packet| le16(packet, offset)).max()?;
+            let high_min = high
+                .iter()
+                .filter_map(|packet| le16(packet, offset))
+                .min()?;
+            let steady_values = steady.iter().filter_map(|packet| le16(packet, offset));
+            let steady_range = steady_values.clone().max().unwrap_or(0) as i64
+                - steady_values.min().unwrap_or(0) as i64;
+            Some((offset, high_min as i64 - low_max as i64 - steady_range))
+        })
+        .filter(|(_, score)| *score > 0)
+        .max_by_key(|(_, score)| *score)
+        .map(|(offset, _)| offset)
+}
+
+/// Bit set in most of `packets` but never in `baseline`, skipping bytes in `used`
+fn button_bit(packets: &[&Vec<u8>], baseline: &[&Vec<u8>], used: &[usize]) -> Option<(usize, u8)> {
+    let length = packets.iter().map(|packet| packet.len()).min()?;
+    let mut best = None;
+    let mut best_share = MIN_BUTTON_SHARE;
+    for byte in (1..length).filter(|byte| !used.contains(byte)) {
+        for bit in 0..8 {
+            let in_baseline = baseline
+                .iter()
+                .any(|packet| packet.get(byte).is_some_and(|value| value.is_bit_set(bit)));
+            if in_baseline {
+                continue;
+            }
+            let set = packets
+                .iter()
+                .filter(|packet| packet[byte].is_bit_set(bit))
+                .count();
+            let share = set as f32 / packets.len() as f32;
+            if share > best_share {
+                best_share = share;
+                best = Some((byte, bit));
+            }
+        }
+    }
+    best
+}
+
+/// Infers a device info entry from a recorded session
+pub(crate) fn analyse(id: DeviceID, session: &Session) -> Result<Map<String, Value>, String> {
+    let corners: Vec<&Vec<u8>> = session
+        .top_left
+        .iter()
+        .chain(&session.top_right)
+        .chain(&session.bottom_right)
+        .collect();
+    let pen = infer_match(&corners).ok_or("No packets were recorded at the corners.")?;
+    let pen_packets = |packets| pen.filter(packets);
+    let top_left = pen_packets(&session.top_left);
+    let top_right = pen_packets(&session.top_right);
+    let bottom_right = pen_packets(&session.bottom_right);
+
+    // the tilt step keeps the pen in place while other values change
+    let tilt_packets = pen_packets(&session.tilt);
+    let mut used = vec![0];
+    let x = separating_field(&top_left, &top_right, &tilt_packets, &used)
+        .ok_or("Failed to find the X axis, did the pen move from left to right?")?;
+    used.extend([x, x + 1]);
+    let y = separating_field(&top_right, &bottom_right, &tilt_packets, &used)
+        .ok_or("Failed to find the Y axis, did the pen move from top to bottom?")?;
+    used.extend([y, y + 1]);
+    let width = top_right
+        .iter()
+        .chain(&bottom_right)
+        .filter_map(|packet| le16(packet, x))
+        .max()
+        .unwrap_or(0);
+    let height = bottom_right
+        .iter()
+        .filter_map(|packet| le16(packet, y))
+        .max()
+        .unwrap_or(0);
+    info!(
+        "X at byte {}, up to {}; Y at byte {}, up to {}.",
+        x, width, y, height
+    );
+
+    let mut layout = pen.to_json();
+    layout.insert("x".to_string(), json!({ "offset": x, "bits": 16 }));
+    layout.insert("y".to_string(), json!({ "offset": y, "bits": 16 }));
+    let mut entry = Map::new();
+    entry.insert("id".to_string(), json!(id.to_string()));
+    entry.insert("width".to_string(), json!(width));
+    entry.insert("height".to_string(), json!(height));
+    let mut button_available = vec!["PenTip".to_string()];
+
+    let pressure_packets = pen_packets(&session.pressure);
+    let pressure = pressure_packets
+        .iter()
+        .map(|packet| packet.len())
+        .min()
+        .and_then(|length| {
+            (2..length.saturating_sub(1))
+                .filter(|offset| !used.contains(offset) && !used.contains(&(offset + 1)))
+                .filter_map(|offset| {
+                    let values = pressure_packets
+                        .iter()
+                        .filter_map(|packet| le16(packet, offset));
+                    let max = values.clone().max()?;
+                    let min = values.min()?;
+                    Some((offset, min, max))
+                })
+                .max_by_key(|(_, min, max)| max - min)
+                .filter(|(_, min, max)| max > min)
+        });
+    match pressure {
+        Some((offset, _, max)) => {
+            // devices report up to a power of two minus one
+            let pressure_max = (max + 1).next_power_of_two() - 1;
+            info!("Pressure at byte {}, up to {}.", offset, pressure_max);
+            layout.insert(
+                "pressure".to_string(),
+                json!({ "offset": offset, "bits": 16 }),
+            );
+            entry.insert("pressure_max".to_string(), json!(pressure_max));
+            used.extend([offset, offset + 1]);
+        }
+        None => warn!("Failed to find pressure, was the tip pressed?"),
+    }
+
+    let mut tilt_bytes: Vec<(usize, i32)> = tilt_packets
+        .iter()
+        .map(|packet| packet.len())
+        .min()
+        .map(|length| {
+            (2..length)
+                .filter(|offset| !used.contains(offset))
+                .filter_map(|offset| {
+                    let values = tilt_packets
+                        .iter()
+                        .map(|packet| packet[offset] as i8 as i32);
+                    let range = values.clone().max()? - values.min()?;
+                    (range >= MIN_TILT_RANGE).then_some((offset, range))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    tilt_bytes.sort_by_key(|(_, range)| -range);
+    if let [(first, _), (second, _), ..] = tilt_bytes.as_slice() {
+        let (tilt_x, tilt_y) = (*first.min(second), *first.max(second));
+        info!("Tilt at bytes {} and {}.", tilt_x, tilt_y);
+        layout.insert(
+            "tilt_x".to_string(),
+            json!({ "offset": tilt_x, "bits": 8, "signed": true }),
+        );
+        layout.insert(
+            "tilt_y".to_string(),
+            json!({ "offset": tilt_y, "bits": 8, "signed": true }),
+        );
+        used.extend([tilt_x, tilt_y]);
+    }
+
+    // buttons share the status byte, only bytes of fields are off limits
+    let mut buttons = Vec::new();
+    let hovering: Vec<&Vec<u8>> = corners
+        .iter()
+        .copied()
+        .filter(|packet| pen.matches(packet))
+        .collect();
+    for (button, packets) in PEN_BUTTONS.iter().zip(&session.pen_buttons) {
+        let packets = pen_packets(packets);
+        if packets.is_empty() {
+            continue;
+        }
+        match button_bit(&packets, &hovering, &used) {
+            Some((byte, bit)) => {
+                info!("{} at byte {} bit {}.", button, byte, bit);
+                buttons.push(json!({ "byte": byte, "bit": bit, "button": button }));
+                button_available.push(button.to_string());
+            }
+            None => warn!("Failed to find the {} button.", button),
+        }
+    }
+    layout.insert("buttons".to_string(), Value::Array(buttons));
+    let mut reports = vec![Value::Object(layout)];
+
+    let pad_packets: Vec<&Vec<u8>> = session
+        .pad_buttons
+        .iter()
+        .flatten()
+        .filter(|packet| !pen.matches(packet))
+        .collect();
+    if let Some(pad) = infer_match(&pad_packets) {
+        let idle = pad.filter(&session.idle);
+        let mut pad_buttons = Vec::new();
+        for (index, packets) in session.pad_buttons.iter().enumerate() {
+            let packets = pad.filter(packets);
+            // a held button is never part of the baseline of another one
+            let baseline: Vec<&Vec<u8>> = session
+                .pad_buttons
+                .iter()
+                .enumerate()
+                .filter(|(other, _)| *other != index)
+                .flat_map(|(_, packets)| pad.filter(packets))
+                .chain(idle.iter().copied())
+                .collect();
+            let button = format!("Tablet{}", index);
+            match button_bit(&packets, &baseline, &[0]) {
+                Some((byte, bit)) => {
+                    info!("{} at byte {} bit {}.", button, byte, bit);
+                    pad_buttons.push(json!({ "byte": byte, "bit": bit, "button": button }));
+                    button_available.push(button);
+                }
+                None => warn!("Failed to find pad button {}.", index + 1),
+            }
+        }
+        let mut layout = pad.to_json();
+        layout.insert("buttons".to_string(), Value::Array(pad_buttons));
+        reports.push(Value::Object(layout));
+    } else if !session.pad_buttons.is_empty() {
+        warn!("No pad reports were recorded.");
+    }
+
+    let packet_length = corners
+        .iter()
+        .chain(&pad_packets)
+        .map(|packet| packet.len())
+        .max()
+        .unwrap_or(0);
+    entry.insert("packet_length".to_string(), json!(packet_length));
+    entry.insert("button_available".to_string(), json!(button_available));
+    entry.insert("reports".to_string(), Value::Array(reports));
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{analyse, Session};
+    use crate::interfaces::DeviceID;
+
+    const STATUS_HOVER: u8 = 0x80;
+    const STATUS_TIP: u8 = 0x81;
+
+    /// Huion v2 style pen packet, 16 bit little endian axes and signed tilt at the end
+    fn pen(status: u8, (x, y): (u16, u16), pressure: u16, (tilt_x, tilt_y): (i8, i8)) -> Vec<u8> {
+        let [x_low, x_high] = x.to_le_bytes();
+        let [y_low, y_high] = y.to_le_bytes();
+        let [pressure_low, pressure_high] = pressure.to_le_bytes();
+        vec![
+            0x08,
+            status,
+            x_low,
+            x_high,
+            y_low,
+            y_high,
+            pressure_low,
+            pressure_high,
+            0,
+            0,
+            tilt_x as u8,
+            tilt_y as u8,
+        ]
+    }
+
+    /// Huion v2 style pad packet with the buttons as a bitmap in byte 4
+    fn pad(buttons: u8) -> Vec<u8> {
+        vec![0x08, 0xe0, 0x01, 0x01, buttons, 0, 0, 0, 0, 0, 0, 0]
+    }
+
+    /// A few hovering packets jittering around `position`
+    fn hover(position: (u16, u16)) -> Vec<Vec<u8>> {
+        [(0, 0), (3, 1), (1, 4), (5, 2)]
+            .into_iter()
+            .map(|(dx, dy)| pen(STATUS_HOVER, (position.0 + dx, position.1 + dy), 0, (0, 0)))
+            .collect()
+    }
+
+    /// Held three packets long and released
+    fn pad_press(index: u8) -> Vec<Vec<u8>> {
+        vec![pad(1 << index), pad(1 << index), pad(1 << index), pad(0)]
+    }
+
+    fn huion_session() -> Session {
+        let center = (25000, 15000);
+        Session {
+            idle: vec![pad(0), pad(0)],
+            top_left: hover((120, 80)),
+            top_right: hover((50700, 90)),
+            bottom_right: hover((50690, 31700)),
+            pressure: [0, 900, 2600, 5100, 8000, 3000, 0]
+                .into_iter()
+                .map(|pressure| pen(STATUS_TIP, center, pressure, (0, 0)))
+                .collect(),
+            tilt: [(-40, -20), (-10, 30), (0, 0), (25, -15), (40, 10)]
+                .into_iter()
+                .map(|tilt| pen(STATUS_HOVER, center, 0, tilt))
+                .collect(),
+            pen_buttons: [0x82, 0x84]
+                .into_iter()
+                .map(|status| vec![pen(status, center, 0, (0, 0)); 3])
+                .collect(),
+            pad_buttons: (0..4).map(pad_press).collect(),
+        }
+    }
+
+    #[test]
+    fn huion_session_layout() {
+        let id = DeviceID {
+            vid: 0x256c,
+            pid: 0x006d,
+        };
+        let entry = analyse(id, &huion_session()).unwrap();
+        assert_eq!(
+            Value::Object(entry),
+            json!({
+                "id": "256c:006d",
+                "width": 50705,
+                "height": 31704,
+                "pressure_max": 8191,
+                "packet_length": 12,
+                "button_available": [
+                    "PenTip", "PenPrimary", "PenSecondary",
+                    "Tablet0", "Tablet1", "Tablet2", "Tablet3"
+                ],
+                "reports": [
+                    {
+                        "report_id": 8,
+                        "discriminator": { "byte": 1, "mask": 0xf0, "value": 0x80 },
+                        "x": { "offset": 2, "bits": 16 },
+                        "y": { "offset": 4, "bits": 16 },
+                        "pressure": { "offset": 6, "bits": 16 },
+                        "tilt_x": { "offset": 10, "bits": 8, "signed": true },
+                        "tilt_y": { "offset": 11, "bits": 8, "signed": true },
+                        "buttons": [
+                            { "byte": 1, "bit": 1, "button": "PenPrimary" },
+                            { "byte": 1, "bit": 2, "button": "PenSecondary" }
+                        ]
+                    },
+                    {
+                        "report_id": 8,
+                        "discriminator": { "byte": 1, "mask": 0xf0, "value": 0xe0 },
+                        "buttons": [
+                            { "byte": 4, "bit": 0, "button": "Tablet0" },
+                            { "byte": 4, "bit": 1, "button": "Tablet1" },
+                            { "byte": 4, "bit": 2, "button": "Tablet2" },
+                            { "byte": 4, "bit": 3, "button": "Tablet3" }
+                        ]
+                    }
+                ]
+            })
+        );
+    }
+}